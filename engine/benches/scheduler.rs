@@ -0,0 +1,137 @@
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use crossbeam::channel::{self, Sender};
+
+use graph::error::GraphError;
+use graph::graph::Graph;
+use graph::schema::Schema;
+use graph::value::Value;
+
+use engine::dispatcher::Dispatcher;
+use engine::execution::{Execution, ExecutionHandle};
+use engine::library::{Library, Values};
+use engine::message::{Instruction, Message};
+use engine::plan::ExecutionPlan;
+use engine::scheduler::SchedulingPolicy;
+use engine::{Engine, EngineConfig};
+
+const EXECUTIONS: u64 = 16;
+const FAN_OUT: i64 = 100;
+/// Worker threads of the baseline, as many as `pool_size` in `config.toml`.
+const WORKERS: usize = 3;
+
+/// Builds a graph that dispatches `FAN_OUT + 2` instructions when `a1` is triggered.
+fn build_graph(schema: &Schema) -> Result<Graph, GraphError> {
+    let mut gb = Graph::builder(schema);
+    let a1 = gb.node("action", "a1")?;
+    let r1 = gb.node("repeat", "r1")?;
+    let r2 = gb.node("repeat", "r2")?;
+
+    gb.assign(&r1, "times", Value::Integer(FAN_OUT))?;
+    gb.assign(&r2, "times", Value::Integer(1))?;
+
+    gb.connect(&a1, "triggered", &r1, "start")?;
+    gb.connect(&r1, "executed", &r2, "start")?;
+
+    gb.build()
+}
+
+/// Workers sharing one MPMC channel, with no fairness or priorities, as a baseline for
+/// the scheduler.
+struct Channel {
+    sender: Sender<Message>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl Channel {
+    fn new(library: &Arc<Library>) -> Self {
+        let (sender, receiver) = channel::unbounded();
+        let workers = (0..WORKERS)
+            .map(|_| {
+                let dispatcher = Dispatcher::new(library).unwrap();
+                let (sender, receiver) = (sender.clone(), receiver.clone());
+                thread::spawn(move || {
+                    while let Ok(Message::Instruction(instruction)) = receiver.recv() {
+                        dispatcher.handle(instruction, |next| {
+                            sender.send(Message::Instruction(next)).unwrap();
+                            Ok(())
+                        });
+                    }
+                })
+            })
+            .collect();
+        Channel { sender, workers }
+    }
+
+    fn execute(&self, id: u64, plan: &Arc<ExecutionPlan>) -> ExecutionHandle {
+        let trigger = plan.find_command("a1", "trigger").unwrap();
+        let execution = Arc::new(Execution::new(id, Arc::clone(plan)));
+        execution.admit(0).unwrap();
+        execution.dispatched();
+        let instruction = Instruction::new(Arc::clone(&execution), trigger, Values::new());
+        self.sender.send(Message::Instruction(instruction)).unwrap();
+        ExecutionHandle::new(execution, vec![trigger])
+    }
+}
+
+impl Drop for Channel {
+    fn drop(&mut self) {
+        for _ in self.workers.iter() {
+            self.sender.send(Message::Stop).unwrap();
+        }
+        for worker in self.workers.drain(..) {
+            worker.join().unwrap();
+        }
+    }
+}
+
+fn throughput(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scheduler");
+    group.throughput(Throughput::Elements(EXECUTIONS * (FAN_OUT as u64 + 2)));
+
+    let policies = [
+        ("round-robin", SchedulingPolicy::RoundRobin),
+        ("weighted-fair", SchedulingPolicy::WeightedFair),
+        ("work-stealing", SchedulingPolicy::WorkStealing),
+    ];
+    for (name, policy) in policies.iter() {
+        let library = Library::get();
+        let graph = build_graph(&library.schema).unwrap();
+        let mut config = EngineConfig::load().unwrap();
+        config.scheduler.policy = *policy;
+        let mut engine = Engine::new(config, library);
+        engine.run();
+
+        group.bench_with_input(BenchmarkId::from_parameter(name), &graph, |b, graph| {
+            b.iter(|| {
+                let handles: Vec<_> = (0..EXECUTIONS)
+                    .map(|_| engine.execute(graph.clone(), "a1", "trigger").unwrap())
+                    .collect();
+                for handle in handles {
+                    handle.join();
+                }
+            })
+        });
+    }
+
+    let library = Arc::new(Library::get());
+    let graph = build_graph(&library.schema).unwrap();
+    let plan = Arc::new(ExecutionPlan::compile(&graph, &library).unwrap());
+    let channel = Channel::new(&library);
+    group.bench_with_input(BenchmarkId::from_parameter("channel"), &plan, |b, plan| {
+        b.iter(|| {
+            let handles: Vec<_> = (0..EXECUTIONS)
+                .map(|id| channel.execute(id, plan))
+                .collect();
+            for handle in handles {
+                handle.join();
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, throughput);
+criterion_main!(benches);
@@ -0,0 +1,476 @@
+//! Checkpoints of executions that allow resuming them after a restart.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Debug, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use serde_json::json;
+
+use graph::graph::port::Port;
+use graph::graph::Graph;
+use graph::schema::Schema;
+
+use crate::error::EngineError;
+use crate::execution::{Execution, ExecutionId, ExecutionLimits, Priority};
+use crate::json::{
+    get_array, get_string, get_u64, value_from_json, value_to_json, values_from_json,
+    values_to_json,
+};
+use crate::library::Values;
+use crate::message::Instruction;
+use crate::retry::{self, RetryPolicy};
+
+/// Instruction that was dispatched but not yet handled when a checkpoint was taken.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingInstruction {
+    /// Key of the instructed node.
+    pub node: String,
+    /// Id of the command.
+    pub command: String,
+    /// Payload of the instruction.
+    pub payload: Values,
+    /// Number of edges followed from an entry instruction.
+    pub depth: usize,
+    /// Edge that was followed to the command, unless it is an entry point.
+    pub edge: Option<String>,
+}
+
+/// State of an unfinished execution. The graph it executes is saved separately, once.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    /// Id of the execution.
+    pub execution: ExecutionId,
+    /// Node keys and command ids that started the execution.
+    pub entries: Vec<(String, String)>,
+    /// Limits enforced on the execution.
+    pub limits: ExecutionLimits,
+    /// Retry policies overriding those of placed nodes' schemas, by node keys.
+    pub retry_policies: HashMap<String, RetryPolicy>,
+    /// Scheduling priority of the execution.
+    pub priority: Priority,
+    /// Time the execution ran for, counted against its max wall time once resumed.
+    pub elapsed: Duration,
+    /// Number of instructions dispatched so far.
+    pub steps: usize,
+    /// States of nodes by node keys.
+    pub states: HashMap<String, Values>,
+    /// Instructions left to handle.
+    pub pending: Vec<PendingInstruction>,
+}
+
+/// Persists checkpoints of executions.
+pub trait CheckpointStore: Send + Sync {
+    /// Saves the graph of an execution when it starts, before its first checkpoint.
+    fn save_graph(&self, execution: ExecutionId, graph: &Graph) -> Result<(), EngineError>;
+
+    /// Saves a checkpoint, replacing the previous checkpoint of the same execution.
+    fn save(&self, checkpoint: &Checkpoint) -> Result<(), EngineError>;
+
+    /// Removes the checkpoint and graph of an execution, if there are any.
+    fn remove(&self, execution: ExecutionId) -> Result<(), EngineError>;
+
+    /// Returns ids of executions with a saved checkpoint.
+    fn executions(&self) -> Result<Vec<ExecutionId>, EngineError>;
+
+    /// Loads all checkpoints.
+    fn load(&self) -> Result<Vec<Checkpoint>, EngineError>;
+
+    /// Loads the graph of an execution, rebuilding it from a schema.
+    fn load_graph(&self, execution: ExecutionId, schema: &Schema) -> Result<Graph, EngineError>;
+}
+
+/// Stores checkpoints as JSON files in a directory, with a checkpoint file and a graph file
+/// per execution.
+#[derive(Debug)]
+pub struct FileStore {
+    directory: PathBuf,
+}
+
+/// Keeps track of pending instructions of an execution and saves its checkpoints.
+pub(crate) struct Checkpointer {
+    store: Arc<dyn CheckpointStore>,
+    entries: Vec<(String, String)>,
+    state: Mutex<State>,
+}
+
+struct State {
+    next_sequence: u64,
+    pending: BTreeMap<u64, PendingInstruction>,
+    removed: bool,
+}
+
+impl PendingInstruction {
+    /// Constructs a `PendingInstruction` describing an instruction.
+    pub fn new(instruction: &Instruction) -> Self {
+        let plan = &instruction.context.plan;
+        let command = &plan.commands[instruction.command];
+        PendingInstruction {
+            node: plan.nodes[command.node].key.clone(),
+            command: command.reference.property.id.clone(),
+            payload: instruction.payload.clone(),
+            depth: instruction.depth,
+            edge: instruction.edge.clone(),
+        }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "node": self.node,
+            "command": self.command,
+            "payload": values_to_json(&self.payload),
+            "depth": self.depth,
+            "edge": self.edge,
+        })
+    }
+
+    fn from_json(json: &serde_json::Value) -> Result<Self, EngineError> {
+        Ok(PendingInstruction {
+            node: get_string(json, "node", "checkpoint")?,
+            command: get_string(json, "command", "checkpoint")?,
+            payload: values_from_json(&json["payload"])?,
+            depth: get_u64(json, "depth", "checkpoint")? as usize,
+            edge: json["edge"].as_str().map(String::from),
+        })
+    }
+}
+
+impl Checkpoint {
+    /// Returns the checkpoint as a JSON object.
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "execution": self.execution,
+            "entries": self.entries.iter().map(|(node, command)| json!({
+                "node": node,
+                "command": command,
+            })).collect::<Vec<_>>(),
+            "limits": {
+                "max_steps": self.limits.max_steps,
+                "max_depth": self.limits.max_depth,
+                "max_wall_time_ms": self.limits.max_wall_time_ms,
+            },
+            "retry_policies": self.retry_policies.iter().map(|(key, policy)| {
+                (key.clone(), retry::to_json(policy))
+            }).collect::<serde_json::Map<_, _>>(),
+            "priority": self.priority,
+            "elapsed_ms": self.elapsed.as_millis() as u64,
+            "steps": self.steps,
+            "states": self.states.iter().map(|(key, state)| {
+                (key.clone(), values_to_json(state))
+            }).collect::<serde_json::Map<_, _>>(),
+            "pending": self.pending.iter().map(PendingInstruction::to_json).collect::<Vec<_>>(),
+        })
+    }
+
+    /// Reads a checkpoint from a JSON object.
+    pub fn from_json(json: &serde_json::Value) -> Result<Self, EngineError> {
+        let entries = get_array(json, "entries", "checkpoint")?
+            .iter()
+            .map(|entry| {
+                Ok((
+                    get_string(entry, "node", "checkpoint")?,
+                    get_string(entry, "command", "checkpoint")?,
+                ))
+            })
+            .collect::<Result<_, EngineError>>()?;
+        let pending = get_array(json, "pending", "checkpoint")?
+            .iter()
+            .map(PendingInstruction::from_json)
+            .collect::<Result<_, _>>()?;
+        let states = json["states"]
+            .as_object()
+            .ok_or_else(|| EngineError::new("Invalid checkpoint: missing 'states'."))?
+            .iter()
+            .map(|(key, state)| Ok((key.clone(), values_from_json(state)?)))
+            .collect::<Result<_, EngineError>>()?;
+        let limits = &json["limits"];
+        let retry_policies = json["retry_policies"]
+            .as_object()
+            .ok_or_else(|| EngineError::new("Invalid checkpoint: missing 'retry_policies'."))?
+            .iter()
+            .map(|(key, policy)| Ok((key.clone(), retry::from_json(policy)?)))
+            .collect::<Result<_, EngineError>>()?;
+        Ok(Checkpoint {
+            execution: get_u64(json, "execution", "checkpoint")?,
+            entries,
+            limits: ExecutionLimits {
+                max_steps: limits["max_steps"].as_u64().map(|steps| steps as usize),
+                max_depth: limits["max_depth"].as_u64().map(|depth| depth as usize),
+                max_wall_time_ms: limits["max_wall_time_ms"].as_u64(),
+            },
+            retry_policies,
+            priority: get_u64(json, "priority", "checkpoint")? as Priority,
+            elapsed: Duration::from_millis(get_u64(json, "elapsed_ms", "checkpoint")?),
+            steps: get_u64(json, "steps", "checkpoint")? as usize,
+            states,
+            pending,
+        })
+    }
+}
+
+impl FileStore {
+    /// Constructs a `FileStore` in a directory, creating it if needed.
+    pub fn new<P: AsRef<Path>>(directory: P) -> Result<Self, EngineError> {
+        let directory = directory.as_ref().to_path_buf();
+        fs::create_dir_all(&directory).map_err(|e| {
+            EngineError::from(format!(
+                "Failed to create checkpoint directory '{}': {}",
+                directory.display(),
+                e
+            ))
+        })?;
+        Ok(FileStore { directory })
+    }
+
+    fn path(&self, execution: ExecutionId) -> PathBuf {
+        self.directory.join(format!("execution-{}.json", execution))
+    }
+
+    fn graph_path(&self, execution: ExecutionId) -> PathBuf {
+        self.directory.join(format!("graph-{}.json", execution))
+    }
+
+    fn write(&self, path: &Path, json: serde_json::Value) -> Result<(), EngineError> {
+        // Writes to a temporary file first, so a crash never leaves a partial checkpoint.
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, json.to_string())
+            .and_then(|_| fs::rename(&temporary, path))
+            .map_err(|e| {
+                EngineError::from(format!(
+                    "Failed to save checkpoint to '{}': {}",
+                    path.display(),
+                    e
+                ))
+            })
+    }
+
+    fn read(&self, path: &Path) -> Result<serde_json::Value, EngineError> {
+        let content = fs::read_to_string(path).map_err(|e| {
+            EngineError::from(format!(
+                "Failed to load checkpoint from '{}': {}",
+                path.display(),
+                e
+            ))
+        })?;
+        serde_json::from_str(&content)
+            .map_err(|e| EngineError::from(format!("Invalid checkpoint: {}", e)))
+    }
+}
+
+impl CheckpointStore for FileStore {
+    fn save_graph(&self, execution: ExecutionId, graph: &Graph) -> Result<(), EngineError> {
+        self.write(&self.graph_path(execution), graph_to_json(graph))
+    }
+
+    fn save(&self, checkpoint: &Checkpoint) -> Result<(), EngineError> {
+        self.write(&self.path(checkpoint.execution), checkpoint.to_json())
+    }
+
+    fn remove(&self, execution: ExecutionId) -> Result<(), EngineError> {
+        // Removes the checkpoint first, so a crash never leaves one without its graph.
+        for path in [self.path(execution), self.graph_path(execution)].iter() {
+            match fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    return Err(EngineError::from(format!(
+                        "Failed to remove checkpoint '{}': {}",
+                        path.display(),
+                        e
+                    )))
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn executions(&self) -> Result<Vec<ExecutionId>, EngineError> {
+        let read_error = |e: std::io::Error| {
+            EngineError::from(format!(
+                "Failed to read checkpoint directory '{}': {}",
+                self.directory.display(),
+                e
+            ))
+        };
+        let mut executions = Vec::new();
+        for entry in fs::read_dir(&self.directory).map_err(read_error)? {
+            let name = entry.map_err(read_error)?.file_name();
+            let execution = name
+                .to_str()
+                .and_then(|name| name.strip_prefix("execution-"))
+                .and_then(|name| name.strip_suffix(".json"))
+                .and_then(|id| id.parse::<ExecutionId>().ok());
+            executions.extend(execution);
+        }
+        executions.sort_unstable();
+        Ok(executions)
+    }
+
+    fn load(&self) -> Result<Vec<Checkpoint>, EngineError> {
+        self.executions()?
+            .into_iter()
+            .map(|execution| Checkpoint::from_json(&self.read(&self.path(execution))?))
+            .collect()
+    }
+
+    fn load_graph(&self, execution: ExecutionId, schema: &Schema) -> Result<Graph, EngineError> {
+        graph_from_json(&self.read(&self.graph_path(execution))?, schema)
+    }
+}
+
+impl Debug for Checkpointer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Checkpointer")
+            .field("entries", &self.entries)
+            .field("pending", &self.lock().pending.len())
+            .finish()
+    }
+}
+
+impl Checkpointer {
+    /// Constructs a `Checkpointer` of an execution whose graph is already saved.
+    pub(crate) fn new(store: Arc<dyn CheckpointStore>, entries: Vec<(String, String)>) -> Self {
+        Checkpointer {
+            store,
+            entries,
+            state: Mutex::new(State {
+                next_sequence: 0,
+                pending: BTreeMap::new(),
+                removed: false,
+            }),
+        }
+    }
+
+    /// Adds an instruction about to be dispatched to pending ones.
+    pub(crate) fn track(&self, instruction: &mut Instruction) {
+        let mut state = self.lock();
+        let sequence = state.next_sequence;
+        state.next_sequence += 1;
+        state
+            .pending
+            .insert(sequence, PendingInstruction::new(instruction));
+        instruction.sequence = Some(sequence);
+    }
+
+    /// Removes a handled instruction from pending ones.
+    pub(crate) fn untrack(&self, instruction: &Instruction) {
+        if let Some(sequence) = instruction.sequence {
+            self.lock().pending.remove(&sequence);
+        }
+    }
+
+    /// Saves a checkpoint of pending instructions, unless the execution has finished.
+    pub(crate) fn save(&self, execution: &Execution) -> Result<(), EngineError> {
+        let steps = execution.steps();
+        let states = execution.node_states();
+        let state = self.lock();
+        if state.removed {
+            return Ok(());
+        }
+        self.store.save(&Checkpoint {
+            execution: execution.id(),
+            entries: self.entries.clone(),
+            limits: *execution.limits(),
+            retry_policies: execution.retry_policies().clone(),
+            priority: execution.priority(),
+            elapsed: execution.elapsed(),
+            steps,
+            states,
+            pending: state.pending.values().cloned().collect(),
+        })
+    }
+
+    /// Removes the checkpoint of a finished execution. No checkpoints are saved afterwards.
+    pub(crate) fn remove(&self, execution: ExecutionId) -> Result<(), EngineError> {
+        let mut state = self.lock();
+        state.removed = true;
+        self.store.remove(execution)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn graph_to_json(graph: &Graph) -> serde_json::Value {
+    let mut nodes: Vec<_> = graph.nodes.values().collect();
+    nodes.sort_by(|a, b| a.key.cmp(&b.key));
+    let mut edges: Vec<_> = graph.edge_map.edges.values().collect();
+    edges.sort_by_key(|edge| edge.to_string());
+    let port_to_json =
+        |port: &Port| json!({ "id": port.id, "node": port.key, "property": port.property_id });
+    json!({
+        "nodes": nodes.iter().map(|node| json!({
+            "id": node.node.id,
+            "key": node.key,
+            "values": node.values.values().map(|value| {
+                (value.property_id.clone(), value_to_json(&value.value))
+            }).collect::<serde_json::Map<_, _>>(),
+            "retry": node.retry.as_ref().map(retry::to_json),
+        })).collect::<Vec<_>>(),
+        "edges": edges.iter().map(|edge| json!({
+            "source": edge.source.node.key,
+            "source_property": edge.source.property.id(),
+            "target": edge.target.node.key,
+            "target_property": edge.target.property.id(),
+            "guard": edge.guard.as_ref().map(|guard| guard.to_string()),
+        })).collect::<Vec<_>>(),
+        "parameters": graph.parameters.values().map(port_to_json).collect::<Vec<_>>(),
+        "results": graph.results.values().map(port_to_json).collect::<Vec<_>>(),
+    })
+}
+
+fn graph_from_json(json: &serde_json::Value, schema: &Schema) -> Result<Graph, EngineError> {
+    let mut gb = Graph::builder(schema);
+    let mut nodes = HashMap::new();
+    for node in get_array(json, "nodes", "checkpoint")? {
+        let placed_node = gb.node(
+            &get_string(node, "id", "checkpoint")?,
+            &get_string(node, "key", "checkpoint")?,
+        )?;
+        let values = node["values"]
+            .as_object()
+            .ok_or_else(|| EngineError::new("Invalid checkpoint: missing 'values'."))?;
+        for (property_id, value) in values {
+            gb.assign(&placed_node, property_id, value_from_json(value)?)?;
+        }
+        if !node["retry"].is_null() {
+            gb.retry(&placed_node, retry::from_json(&node["retry"])?)?;
+        }
+        nodes.insert(placed_node.key.clone(), placed_node);
+    }
+    let find = |json: &serde_json::Value, id: &str| {
+        let key = get_string(json, id, "checkpoint")?;
+        nodes.get(&key).ok_or_else(|| {
+            EngineError::from(format!("Invalid checkpoint: node '{}' is missing.", key))
+        })
+    };
+
+    for edge in get_array(json, "edges", "checkpoint")? {
+        let (source, target) = (find(edge, "source")?, find(edge, "target")?);
+        let source_property = get_string(edge, "source_property", "checkpoint")?;
+        let target_property = get_string(edge, "target_property", "checkpoint")?;
+        match edge["guard"].as_str() {
+            Some(guard) => {
+                gb.connect_with_guard(source, &source_property, target, &target_property, guard)?
+            }
+            None => gb.connect(source, &source_property, target, &target_property)?,
+        }
+    }
+    for port in get_array(json, "parameters", "checkpoint")? {
+        gb.parameter(
+            &get_string(port, "id", "checkpoint")?,
+            find(port, "node")?,
+            &get_string(port, "property", "checkpoint")?,
+        )?;
+    }
+    for port in get_array(json, "results", "checkpoint")? {
+        gb.result(
+            &get_string(port, "id", "checkpoint")?,
+            find(port, "node")?,
+            &get_string(port, "property", "checkpoint")?,
+        )?;
+    }
+    Ok(gb.build()?)
+}
@@ -0,0 +1,207 @@
+//! Step debugger for executions on a `LocalExecutor`.
+//!
+//! Breakpoints pause an execution once a matching instruction has been dispatched to the
+//! executor's queue and is the next one to be handled, so it can be inspected before its
+//! command runs.
+//!
+//! Only executions on a `LocalExecutor` can be debugged. `Engine` and `AsyncEngine` run
+//! instructions on several threads at once, so they can't pause an execution between
+//! instructions; to debug a graph they run, execute it with a `Debugger` instead.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use graph::graph::edge::Edge;
+use graph::graph::Graph;
+
+use crate::error::EngineError;
+use crate::execution::ExecutionHandle;
+use crate::library::Values;
+use crate::local::LocalExecutor;
+use crate::message::Instruction;
+use crate::resolver::Resolver;
+
+/// Condition on which a debugger pauses before a dispatched instruction is handled.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Breakpoint {
+    /// Pauses before any command of a node with given key.
+    Node(String),
+    /// Pauses before a command of a node.
+    Command {
+        /// Key of the node.
+        node: String,
+        /// Id of the command.
+        command: String,
+    },
+    /// Pauses before a command reached through an edge, in `source#event>target#command` form.
+    Edge(String),
+}
+
+/// Runs executions instruction by instruction on its own `LocalExecutor`, pausing on
+/// breakpoints.
+pub struct Debugger {
+    executor: LocalExecutor,
+    breakpoints: Vec<Breakpoint>,
+}
+
+impl Breakpoint {
+    /// Constructs `Breakpoint::Node`.
+    pub fn node(key: &str) -> Self {
+        Breakpoint::Node(String::from(key))
+    }
+
+    /// Constructs `Breakpoint::Command`.
+    pub fn command(key: &str, command_id: &str) -> Self {
+        Breakpoint::Command {
+            node: String::from(key),
+            command: String::from(command_id),
+        }
+    }
+
+    /// Constructs `Breakpoint::Edge` for a graph's edge.
+    pub fn edge(edge: &Edge) -> Self {
+        Breakpoint::Edge(edge.to_string())
+    }
+
+    /// Returns whether an instruction triggers this breakpoint.
+    pub fn matches(&self, instruction: &Instruction) -> bool {
+        let plan = &instruction.context.plan;
+        let command = &plan.commands[instruction.command];
+        let key = &plan.nodes[command.node].key;
+        match self {
+            Breakpoint::Node(node) => node == key,
+            Breakpoint::Command { node, command: id } => {
+                node == key && *id == command.reference.property.id
+            }
+            Breakpoint::Edge(edge) => instruction.edge.as_ref() == Some(edge),
+        }
+    }
+}
+
+impl Debugger {
+    /// Constructs a `Debugger` that runs executions on an executor.
+    pub fn new(executor: LocalExecutor) -> Self {
+        Debugger {
+            executor,
+            breakpoints: Vec::new(),
+        }
+    }
+
+    /// Returns the underlying executor.
+    pub fn executor(&mut self) -> &mut LocalExecutor {
+        &mut self.executor
+    }
+
+    /// Adds a breakpoint.
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint);
+        }
+    }
+
+    /// Removes a breakpoint. Returns whether it existed.
+    pub fn remove_breakpoint(&mut self, breakpoint: &Breakpoint) -> bool {
+        let len = self.breakpoints.len();
+        self.breakpoints.retain(|b| b != breakpoint);
+        self.breakpoints.len() != len
+    }
+
+    /// Returns set breakpoints.
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    /// Queues a command of a node to start executing a graph. Nothing runs until stepped.
+    pub fn execute(
+        &mut self,
+        graph: Graph,
+        key: &str,
+        command_id: &str,
+    ) -> Result<ExecutionHandle, EngineError> {
+        self.executor.execute(graph, key, command_id)
+    }
+
+    /// Queues every `action` node of a graph. Nothing runs until stepped.
+    pub fn execute_actions(&mut self, graph: Graph) -> Result<ExecutionHandle, EngineError> {
+        self.executor.execute_actions(graph)
+    }
+
+    /// Returns the instruction that will be handled next.
+    pub fn current(&self) -> Option<&Instruction> {
+        self.executor.next()
+    }
+
+    /// Returns queued instructions, starting with the current one.
+    pub fn pending(&self) -> &VecDeque<Instruction> {
+        self.executor.pending()
+    }
+
+    /// Returns whether the current instruction triggers a breakpoint.
+    pub fn is_at_breakpoint(&self) -> bool {
+        self.current()
+            .is_some_and(|instruction| self.hits_breakpoint(instruction))
+    }
+
+    /// Returns values of the current instruction node's inputs by id.
+    ///
+    /// For recorded or replayed executions, non-deterministic nodes give the values the
+    /// handler will see. Otherwise they are computed again when the instruction is handled.
+    pub fn inputs(&self) -> Result<Values, EngineError> {
+        let instruction = self
+            .current()
+            .ok_or_else(|| EngineError::new("No instruction is pending."))?;
+        let plan = &instruction.context.plan;
+        let mut resolver = Resolver::new(plan);
+        if let Some(recorder) = instruction.context.execution.recorder() {
+            resolver.set_preview_tape(Arc::clone(recorder), recorder.upcoming_step());
+        }
+        let mut inputs = Values::new();
+        for slot in plan.nodes[instruction.context.node].inputs.iter() {
+            inputs.insert(
+                plan.slots[*slot].property_id.clone(),
+                resolver.resolve(*slot)?,
+            );
+        }
+        Ok(inputs)
+    }
+
+    /// Handles the current instruction, ignoring breakpoints. Returns `false` if none is pending.
+    pub fn step(&mut self) -> bool {
+        self.executor.step()
+    }
+
+    /// Handles the current instruction and keeps going until the next one triggers a
+    /// breakpoint or nothing is pending. Returns the instruction execution paused at.
+    pub fn resume(&mut self) -> Option<&Instruction> {
+        if !self.executor.step() {
+            return None;
+        }
+        self.run()
+    }
+
+    /// Runs until an instruction triggers a breakpoint or nothing is pending, without
+    /// handling the current instruction if it is at a breakpoint.
+    pub fn run(&mut self) -> Option<&Instruction> {
+        while let Some(instruction) = self.current() {
+            if self.hits_breakpoint(instruction) {
+                break;
+            }
+            self.executor.step();
+        }
+        self.current()
+    }
+
+    /// Cancels executions of pending instructions and drops them.
+    pub fn abort(&mut self) {
+        for instruction in self.executor.pending().iter() {
+            instruction.context.execution.cancel();
+        }
+        self.executor.run_until_idle();
+    }
+
+    fn hits_breakpoint(&self, instruction: &Instruction) -> bool {
+        self.breakpoints
+            .iter()
+            .any(|breakpoint| breakpoint.matches(instruction))
+    }
+}
@@ -0,0 +1,410 @@
+//! Dispatcher runs instructions with processors and collects follow-up instructions.
+
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use graph::value::Value;
+
+use crate::error::EngineError;
+use crate::execution::Execution;
+use crate::library::{Library, Values};
+use crate::message::Instruction;
+use crate::metrics::{CommandLabels, Metrics, Sample};
+use crate::plan::{EventIndex, SlotIndex};
+use crate::processor::action_processor::ActionProcessor;
+use crate::processor::printer_processor::PrinterProcessor;
+use crate::processor::repeat_processor::RepeatProcessor;
+use crate::processor::{Invocation, Processor};
+use crate::record::{Recorder, Step};
+use crate::resolver::Resolver;
+use crate::trace::{TraceEvent, Tracer};
+
+/// Handles instructions with a set of processors.
+pub struct Dispatcher {
+    processors: Vec<Box<dyn Processor>>,
+    /// Index of the processor handling a command, by command keys.
+    routes: Vec<Option<usize>>,
+    tracer: Tracer,
+    metrics: Metrics,
+}
+
+/// Completes an instruction of an execution when dropped, failing the execution if
+/// its handler panicked.
+pub(crate) struct InFlight(pub(crate) Arc<Execution>);
+
+impl Dispatcher {
+    /// Constructs a `Dispatcher` with processors of the basic library.
+    pub fn new(library: &Arc<Library>) -> Result<Self, EngineError> {
+        Ok(Dispatcher::with_processors(vec![
+            Box::new(ActionProcessor::new(library)?),
+            Box::new(PrinterProcessor::new(library)?),
+            Box::new(RepeatProcessor::new(library)?),
+        ]))
+    }
+
+    /// Constructs a `Dispatcher` with given processors.
+    ///
+    /// A command handled by several processors is handled by the first of them.
+    pub fn with_processors(processors: Vec<Box<dyn Processor>>) -> Self {
+        let mut routes = Vec::new();
+        for (index, processor) in processors.iter().enumerate() {
+            for key in processor.router().keys() {
+                if routes.len() <= key {
+                    routes.resize(key + 1, None);
+                }
+                routes[key].get_or_insert(index);
+            }
+        }
+        Dispatcher {
+            processors,
+            routes,
+            tracer: Tracer::default(),
+            metrics: Metrics::default(),
+        }
+    }
+
+    /// Sets a tracer for handled instructions.
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.tracer = tracer;
+    }
+
+    /// Sets metrics of handled instructions.
+    pub fn set_metrics(&mut self, metrics: Metrics) {
+        self.metrics = metrics;
+    }
+
+    /// Returns dispatcher's metrics.
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Returns dispatcher's tracer.
+    pub fn tracer(&self) -> &Tracer {
+        &self.tracer
+    }
+
+    /// Handles an instruction and passes every follow-up instruction to `dispatch`.
+    ///
+    /// Instructions of finished executions are dropped. Errors fail the instruction's
+    /// execution, and the instruction is completed once handled. Checkpointed executions
+    /// are saved after every handled instruction.
+    pub fn handle<F>(&self, instruction: Instruction, dispatch: F)
+    where
+        F: FnMut(Instruction) -> Result<(), EngineError>,
+    {
+        let in_flight = InFlight(Arc::clone(&instruction.context.execution));
+        if in_flight.0.is_finished() {
+            return;
+        }
+        let started = Instant::now();
+        let result = self.execute(&instruction, dispatch);
+        self.observe(&instruction, started, result.is_err());
+        if let Err(e) = result {
+            self.fail(&in_flight.0, e);
+        }
+        if let Some(checkpointer) = in_flight.0.checkpointer() {
+            checkpointer.untrack(&instruction);
+            if let Err(e) = checkpointer.save(&in_flight.0) {
+                self.fail(&in_flight.0, e);
+            }
+        }
+    }
+
+    /// Handles an instruction with a matching processor and dispatches follow-up instructions.
+    ///
+    /// A failed handler is retried according to the retry policy of the instructed node, by
+    /// dispatching the instruction again to be handled once the backoff has passed.
+    pub(crate) fn execute<F>(
+        &self,
+        instruction: &Instruction,
+        mut dispatch: F,
+    ) -> Result<(), EngineError>
+    where
+        F: FnMut(Instruction) -> Result<(), EngineError>,
+    {
+        let plan = &instruction.context.plan;
+        let command = &plan.commands[instruction.command];
+
+        let key = command.reference.key;
+        let handler = self
+            .routes
+            .get(key)
+            .copied()
+            .flatten()
+            .and_then(|index| self.processors[index].router().get(key))
+            .ok_or_else(|| {
+                EngineError::from(format!(
+                    "No handler for '{}#{}'.",
+                    plan.nodes[command.node].key, command.reference.property.id
+                ))
+            })?;
+        let node = &plan.nodes[command.node];
+        let execution = &instruction.context.execution;
+        let tape = tape(instruction)?;
+        execution.check_deadline()?;
+        self.tracer
+            .record(execution.id(), || TraceEvent::command_started(instruction));
+        let mut invocation = Invocation::with_tracer(instruction, &self.tracer);
+        if let Some((recorder, step)) = tape.clone() {
+            invocation.set_tape(recorder, step);
+        }
+        if let Err(e) = handler(&mut invocation) {
+            let error = e.raised_by(&node.key, &command.reference.property.id);
+            return self.retry(instruction, error, tape, dispatch);
+        }
+        let execution = execution.id();
+        self.tracer
+            .record(execution, || TraceEvent::command_finished(instruction));
+
+        let events = invocation.take_events();
+        let continuation = invocation.take_continuation();
+        self.follow(
+            instruction,
+            events,
+            |slot| invocation.resolve(slot),
+            &mut dispatch,
+        )?;
+        match continuation {
+            Some(payload) => self.continue_with(instruction, payload, dispatch),
+            None => Ok(()),
+        }
+    }
+
+    /// Dispatches a failed instruction again, to be handled once its backoff has passed,
+    /// if its node's retry policy allows another attempt. Otherwise recovers from the error.
+    pub(crate) fn retry<F>(
+        &self,
+        instruction: &Instruction,
+        error: EngineError,
+        tape: Option<(Arc<Recorder>, Step)>,
+        dispatch: F,
+    ) -> Result<(), EngineError>
+    where
+        F: FnMut(Instruction) -> Result<(), EngineError>,
+    {
+        let delay = match self.retry_delay(instruction, &error, instruction.attempt) {
+            Some(delay) => delay,
+            None => return self.recover(instruction, error, tape, dispatch),
+        };
+        let mut next = self.again(instruction, instruction.payload.clone());
+        next.attempt = instruction.attempt + 1;
+        next.not_before = Some(next.queued_at + delay);
+        self.redispatch(next, dispatch)
+    }
+
+    /// Returns how long to wait before retrying a failed attempt of an instruction, if its
+    /// node's retry policy allows another attempt, and traces the retry.
+    fn retry_delay(
+        &self,
+        instruction: &Instruction,
+        error: &EngineError,
+        attempt: u32,
+    ) -> Option<Duration> {
+        let plan = &instruction.context.plan;
+        let command = &plan.commands[instruction.command];
+        let node = &plan.nodes[command.node];
+        let execution = &instruction.context.execution;
+        let delay = execution
+            .retry_policy(node)
+            .filter(|retry| retry.is_retriable(error.code.as_deref()))
+            .and_then(|retry| retry.delay(attempt))
+            .filter(|_| !execution.is_finished())?;
+        self.tracer.record(execution.id(), || TraceEvent::Retrying {
+            node: node.key.clone(),
+            command: command.reference.property.id.clone(),
+            attempt,
+            delay,
+            message: error.message.clone(),
+        });
+        Some(delay)
+    }
+
+    /// Dispatches an instruction's command again with a payload, at the same depth and
+    /// without counting it as a step.
+    pub(crate) fn continue_with<F>(
+        &self,
+        instruction: &Instruction,
+        payload: Values,
+        dispatch: F,
+    ) -> Result<(), EngineError>
+    where
+        F: FnMut(Instruction) -> Result<(), EngineError>,
+    {
+        self.redispatch(self.again(instruction, payload), dispatch)
+    }
+
+    /// Returns a copy of an instruction with another payload.
+    fn again(&self, instruction: &Instruction, payload: Values) -> Instruction {
+        let execution = &instruction.context.execution;
+        let mut next = Instruction::new(Arc::clone(execution), instruction.command, payload)
+            .at_depth(instruction.depth);
+        next.edge = instruction.edge.clone();
+        next
+    }
+
+    /// Dispatches an instruction that repeats a handled one, without counting it as a step.
+    fn redispatch<F>(&self, mut next: Instruction, mut dispatch: F) -> Result<(), EngineError>
+    where
+        F: FnMut(Instruction) -> Result<(), EngineError>,
+    {
+        let execution = Arc::clone(&next.context.execution);
+        if execution.is_finished() {
+            return Ok(());
+        }
+        execution.check_deadline()?;
+        execution.dispatched();
+        if let Some(checkpointer) = execution.checkpointer() {
+            checkpointer.track(&mut next);
+        }
+        self.tracer
+            .record(execution.id(), || TraceEvent::dispatched(&next));
+        self.metrics
+            .record(|| Sample::Dispatched(CommandLabels::new(&next)));
+        if let Err(e) = dispatch(next) {
+            execution.completed();
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Fires the error event of a failed instruction's node with the error as payload.
+    ///
+    /// Events fired by the failed handler are discarded. Returns the error if the node
+    /// declares no error event or no command was dispatched for it.
+    pub(crate) fn recover<F>(
+        &self,
+        instruction: &Instruction,
+        error: EngineError,
+        tape: Option<(Arc<Recorder>, Step)>,
+        dispatch: F,
+    ) -> Result<(), EngineError>
+    where
+        F: FnMut(Instruction) -> Result<(), EngineError>,
+    {
+        let plan = &instruction.context.plan;
+        let node = instruction.context.node;
+        let event = match plan.nodes[node]
+            .node
+            .error_event
+            .as_ref()
+            .and_then(|event| plan.get_event_index(node, event))
+        {
+            Some(event) if !plan.events[event].targets.is_empty() => event,
+            _ => return Err(error),
+        };
+
+        self.tracer
+            .record(instruction.context.execution.id(), || TraceEvent::Error {
+                message: error.to_string(),
+            });
+        let mut resolver = Resolver::new(plan);
+        if let Some((recorder, step)) = tape {
+            resolver.set_tape(recorder, step);
+        }
+        let events = vec![(event, error.payload())];
+        match self.follow(instruction, events, |slot| resolver.resolve(slot), dispatch)? {
+            0 => Err(error),
+            _ => Ok(()),
+        }
+    }
+
+    /// Dispatches commands targeted by fired events whose guards hold. Returns the number
+    /// of dispatched instructions.
+    ///
+    /// Nothing is dispatched once instruction's execution has finished.
+    pub(crate) fn follow<R, F>(
+        &self,
+        instruction: &Instruction,
+        events: Vec<(EventIndex, Values)>,
+        mut resolve: R,
+        mut dispatch: F,
+    ) -> Result<usize, EngineError>
+    where
+        R: FnMut(SlotIndex) -> Result<Value, EngineError>,
+        F: FnMut(Instruction) -> Result<(), EngineError>,
+    {
+        let plan = &instruction.context.plan;
+        let execution = &instruction.context.execution;
+        if execution.is_finished() {
+            return Ok(0);
+        }
+
+        let mut dispatched = 0;
+        for (event, payload) in events {
+            let plan_event = &plan.events[event];
+            self.tracer
+                .record(execution.id(), || TraceEvent::EventFired {
+                    node: plan.nodes[plan_event.node].key.clone(),
+                    event: plan_event.reference.property.id.clone(),
+                });
+            for target in plan_event.targets.iter() {
+                if !target.allows(&payload, &mut resolve)? {
+                    continue;
+                }
+                plan.commands[target.command].check_payload(&payload)?;
+                self.tracer
+                    .record(execution.id(), || TraceEvent::EdgeFollowed {
+                        edge: target.edge.clone(),
+                    });
+                execution.admit(instruction.depth + 1)?;
+                execution.dispatched();
+                let mut next =
+                    Instruction::new(Arc::clone(execution), target.command, payload.clone())
+                        .via(&target.edge)
+                        .at_depth(instruction.depth + 1);
+                if let Some(checkpointer) = execution.checkpointer() {
+                    checkpointer.track(&mut next);
+                }
+                self.tracer
+                    .record(execution.id(), || TraceEvent::dispatched(&next));
+                self.metrics
+                    .record(|| Sample::Dispatched(CommandLabels::new(&next)));
+                if let Err(e) = dispatch(next) {
+                    execution.completed();
+                    return Err(e);
+                }
+                dispatched += 1;
+            }
+        }
+
+        Ok(dispatched)
+    }
+
+    /// Fails an execution with an error and traces it.
+    pub(crate) fn fail(&self, execution: &Execution, error: EngineError) {
+        self.tracer.record(execution.id(), || TraceEvent::Error {
+            message: error.to_string(),
+        });
+        execution.fail(error);
+    }
+
+    /// Records metrics of a handled instruction.
+    pub(crate) fn observe(&self, instruction: &Instruction, started: Instant, failed: bool) {
+        self.metrics.record(|| Sample::Handled {
+            labels: CommandLabels::new(instruction),
+            queue_wait: started.saturating_duration_since(instruction.queued_at),
+            duration: started.elapsed(),
+            failed,
+        });
+    }
+}
+
+/// Records that an instruction is handled, if its execution is recorded or replayed.
+pub(crate) fn tape(
+    instruction: &Instruction,
+) -> Result<Option<(Arc<Recorder>, Step)>, EngineError> {
+    match instruction.context.execution.recorder() {
+        Some(recorder) => Ok(Some((Arc::clone(recorder), recorder.handled(instruction)?))),
+        None => Ok(None),
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.0.fail(EngineError::new("Handler panicked."));
+        }
+        self.0.completed();
+    }
+}
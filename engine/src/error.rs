@@ -6,26 +6,87 @@ use std::fmt::{Display, Error, Formatter};
 use config::ConfigError;
 
 use graph::error::GraphError;
+use graph::schema::node::{ERROR_FIELD_CODE, ERROR_FIELD_COMMAND, ERROR_FIELD_MESSAGE};
+use graph::value::Value;
+
+use crate::library::Values;
 
 /// Error representing an error with an engine.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct EngineError {
     /// Error message.
     pub message: String,
+    /// Machine readable code of the error, if any.
+    pub code: Option<String>,
+    /// Command whose handler raised the error, if any.
+    pub origin: Option<ErrorOrigin>,
+}
+
+/// Command of a placed node that failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorOrigin {
+    /// Key of the node.
+    pub node: String,
+    /// Id of the command.
+    pub command: String,
 }
 
 impl EngineError {
     /// Constructs a new `EngineError`.
     pub fn new(message: &str) -> Self {
+        EngineError::from(String::from(message))
+    }
+
+    /// Constructs a new `EngineError` with a machine readable code.
+    pub fn with_code(code: &str, message: &str) -> Self {
         EngineError {
-            message: String::from(message),
+            code: Some(String::from(code)),
+            ..EngineError::new(message)
+        }
+    }
+
+    /// Returns the error marked as raised by a command, unless it already has an origin.
+    pub fn raised_by(mut self, node: &str, command: &str) -> Self {
+        if self.origin.is_none() {
+            self.origin = Some(ErrorOrigin {
+                node: String::from(node),
+                command: String::from(command),
+            });
         }
+        self
+    }
+
+    /// Returns payload of an error event describing the error.
+    pub fn payload(&self) -> Values {
+        let mut payload = Values::new();
+        payload.insert(
+            ERROR_FIELD_MESSAGE.into(),
+            Value::String(self.message.clone()),
+        );
+        payload.insert(
+            ERROR_FIELD_CODE.into(),
+            Value::String(self.code.clone().unwrap_or_default()),
+        );
+        payload.insert(
+            ERROR_FIELD_COMMAND.into(),
+            Value::String(
+                self.origin
+                    .as_ref()
+                    .map(|origin| origin.command.clone())
+                    .unwrap_or_default(),
+            ),
+        );
+        payload
     }
 }
 
 impl From<String> for EngineError {
     fn from(s: String) -> Self {
-        EngineError { message: s }
+        EngineError {
+            message: s,
+            code: None,
+            origin: None,
+        }
     }
 }
 
@@ -43,14 +104,12 @@ impl StdError for EngineError {
 
 impl From<GraphError> for EngineError {
     fn from(e: GraphError) -> Self {
-        EngineError { message: e.message }
+        EngineError::from(e.message)
     }
 }
 
 impl From<ConfigError> for EngineError {
     fn from(e: ConfigError) -> Self {
-        EngineError {
-            message: String::from(e.description()),
-        }
+        EngineError::from(e.to_string())
     }
 }
@@ -0,0 +1,671 @@
+//! Executions of graphs started on an engine.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+use crate::checkpoint::Checkpointer;
+use crate::error::EngineError;
+use crate::library::Values;
+use crate::message::Instruction;
+use crate::metrics::{CommandLabels, Metrics, Sample};
+use crate::plan::{CommandIndex, ExecutionPlan, PlanNode};
+use crate::record::{Recorder, Recording};
+use crate::retry::RetryPolicy;
+use crate::trace::{TraceEvent, Tracer};
+
+/// Unique id of an execution within an engine.
+pub type ExecutionId = u64;
+
+/// Scheduling priority of an execution. Higher values are served first.
+pub type Priority = u8;
+
+/// Final result of an execution.
+#[derive(Debug, Clone)]
+pub enum Outcome {
+    /// All instructions were handled.
+    Succeeded,
+    /// An instruction failed.
+    Failed(EngineError),
+    /// Execution was cancelled.
+    Cancelled,
+}
+
+/// Current status of an execution.
+#[derive(Debug, Clone)]
+pub enum ExecutionStatus {
+    /// Some instructions are still queued or being handled.
+    Running,
+    /// Execution has finished.
+    Finished(Outcome),
+}
+
+/// Limits that abort an execution once exceeded. Unset limits are not enforced.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub struct ExecutionLimits {
+    /// Maximum number of instructions dispatched, including entry instructions.
+    #[serde(default)]
+    pub max_steps: Option<usize>,
+    /// Maximum length of a chain of events following an entry instruction.
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+    /// Maximum time since the start of the execution, in milliseconds.
+    #[serde(default)]
+    pub max_wall_time_ms: Option<u64>,
+}
+
+/// Options for starting an execution.
+#[derive(Debug, Clone, Default)]
+pub struct ExecutionOptions {
+    recorder: Option<Arc<Recorder>>,
+    limits: ExecutionLimits,
+    retry_policies: HashMap<String, RetryPolicy>,
+    priority: Priority,
+}
+
+/// Signals cancellation of an execution to running handlers.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    signal: Arc<(Mutex<()>, Condvar)>,
+}
+
+/// State of an execution shared between an engine, its workers and handles.
+#[derive(Debug)]
+pub struct Execution {
+    id: ExecutionId,
+    plan: Arc<ExecutionPlan>,
+    options: ExecutionOptions,
+    token: CancellationToken,
+    checkpointer: Option<Checkpointer>,
+    started: Instant,
+    state: Mutex<State>,
+    updates: Mutex<()>,
+    finished: Condvar,
+}
+
+#[derive(Debug)]
+struct State {
+    in_flight: usize,
+    steps: usize,
+    node_states: HashMap<String, Values>,
+    finishing: bool,
+    outcome: Option<Outcome>,
+    wakers: Vec<Waker>,
+}
+
+/// Future that resolves to an execution's outcome once it finishes.
+#[derive(Debug)]
+pub struct Completion {
+    execution: Arc<Execution>,
+}
+
+/// Refers to an execution started on an engine.
+#[derive(Debug, Clone)]
+pub struct ExecutionHandle {
+    execution: Arc<Execution>,
+    entries: Vec<CommandIndex>,
+}
+
+impl Execution {
+    /// Constructs an `Execution` with no instructions in flight.
+    pub fn new(id: ExecutionId, plan: Arc<ExecutionPlan>) -> Self {
+        Execution::with_options(id, plan, ExecutionOptions::default())
+    }
+
+    /// Constructs an `Execution` with options and no instructions in flight.
+    pub fn with_options(
+        id: ExecutionId,
+        plan: Arc<ExecutionPlan>,
+        options: ExecutionOptions,
+    ) -> Self {
+        Execution {
+            id,
+            plan,
+            options,
+            token: CancellationToken::new(),
+            checkpointer: None,
+            started: Instant::now(),
+            state: Mutex::new(State {
+                in_flight: 0,
+                steps: 0,
+                node_states: HashMap::new(),
+                finishing: false,
+                outcome: None,
+                wakers: Vec::new(),
+            }),
+            updates: Mutex::new(()),
+            finished: Condvar::new(),
+        }
+    }
+
+    /// Returns execution saving checkpoints with a checkpointer.
+    pub(crate) fn with_checkpointer(mut self, checkpointer: Checkpointer) -> Self {
+        self.checkpointer = Some(checkpointer);
+        self
+    }
+
+    /// Returns execution counting wall time it ran for before it was resumed from a
+    /// checkpoint against its max wall time.
+    pub(crate) fn with_elapsed(mut self, elapsed: Duration) -> Self {
+        self.started = Instant::now().checked_sub(elapsed).unwrap_or(self.started);
+        self
+    }
+
+    /// Returns time since the execution started.
+    pub(crate) fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Returns execution's checkpointer, if it is checkpointed.
+    pub(crate) fn checkpointer(&self) -> Option<&Checkpointer> {
+        self.checkpointer.as_ref()
+    }
+
+    /// Returns execution's id.
+    pub fn id(&self) -> ExecutionId {
+        self.id
+    }
+
+    /// Returns the plan being executed.
+    pub fn plan(&self) -> &Arc<ExecutionPlan> {
+        &self.plan
+    }
+
+    /// Returns execution's recorder, if it is recorded or replayed.
+    pub fn recorder(&self) -> Option<&Arc<Recorder>> {
+        self.options.recorder.as_ref()
+    }
+
+    /// Returns a policy for retrying failed commands of a node, if it has one.
+    pub fn retry_policy<'a>(&'a self, node: &'a PlanNode) -> Option<&'a RetryPolicy> {
+        self.options
+            .retry_policies
+            .get(&node.key)
+            .or(node.retry.as_ref())
+    }
+
+    /// Returns retry policies overriding those of placed nodes, by node keys.
+    pub(crate) fn retry_policies(&self) -> &HashMap<String, RetryPolicy> {
+        &self.options.retry_policies
+    }
+
+    /// Returns execution's scheduling priority.
+    pub fn priority(&self) -> Priority {
+        self.options.priority
+    }
+
+    /// Returns limits enforced on the execution.
+    pub fn limits(&self) -> &ExecutionLimits {
+        &self.options.limits
+    }
+
+    /// Counts an instruction about to be dispatched at a depth against execution's limits.
+    ///
+    /// Fails if any limit is exceeded, in which case the instruction must not be dispatched.
+    pub fn admit(&self, depth: usize) -> Result<(), EngineError> {
+        self.check_deadline()?;
+        let limits = self.limits();
+        if let Some(max_depth) = limits.max_depth {
+            if depth > max_depth {
+                return Err(EngineError::from(format!(
+                    "Execution {} exceeded max depth ({}).",
+                    self.id, max_depth
+                )));
+            }
+        }
+        let mut state = self.lock();
+        self.check_steps_locked(&state, 1)?;
+        state.steps += 1;
+        Ok(())
+    }
+
+    /// Returns the number of instructions dispatched so far.
+    pub fn steps(&self) -> usize {
+        self.lock().steps
+    }
+
+    /// Counts instructions dispatched before the execution was resumed from a checkpoint.
+    pub(crate) fn restore_steps(&self, steps: usize) {
+        self.lock().steps = steps;
+    }
+
+    /// Returns state stored by a node of the execution, empty if it stored none.
+    pub fn node_state(&self, key: &str) -> Values {
+        self.lock()
+            .node_states
+            .get(key)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Updates state of a node of the execution. Concurrent updates of the same execution
+    /// are applied one at a time.
+    ///
+    /// The update runs on a copy of the state without holding execution's lock, so it may
+    /// read or finish the execution, but must not update node states itself.
+    pub fn update_node_state<F, T>(&self, key: &str, update: F) -> T
+    where
+        F: FnOnce(&mut Values) -> T,
+    {
+        let _update = self.updates.lock().unwrap_or_else(|e| e.into_inner());
+        let mut state = self.node_state(key);
+        let result = update(&mut state);
+        self.lock().node_states.insert(String::from(key), state);
+        result
+    }
+
+    /// Returns states of all nodes of the execution by node keys.
+    pub fn node_states(&self) -> HashMap<String, Values> {
+        self.lock().node_states.clone()
+    }
+
+    /// Restores node states of an execution resumed from a checkpoint.
+    pub(crate) fn restore_node_states(&self, states: HashMap<String, Values>) {
+        self.lock().node_states = states;
+    }
+
+    /// Fails if dispatching a number of further instructions would exceed max steps.
+    pub fn check_steps(&self, pending: usize) -> Result<(), EngineError> {
+        self.check_steps_locked(&self.lock(), pending)
+    }
+
+    /// Fails if execution has been running for longer than its max wall time.
+    pub fn check_deadline(&self) -> Result<(), EngineError> {
+        match self.limits().max_wall_time_ms {
+            Some(max_wall_time)
+                if self.started.elapsed() > Duration::from_millis(max_wall_time) =>
+            {
+                Err(EngineError::from(format!(
+                    "Execution {} exceeded max wall time ({} ms).",
+                    self.id, max_wall_time
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Records that an instruction was dispatched.
+    pub fn dispatched(&self) {
+        self.lock().in_flight += 1;
+    }
+
+    /// Records that a dispatched instruction was handled or dropped.
+    ///
+    /// Execution succeeds once no instructions are left in flight.
+    pub fn completed(&self) {
+        let finishing = {
+            let mut state = self.lock();
+            state.in_flight = state.in_flight.saturating_sub(1);
+            state.in_flight == 0 && Self::start_finishing(&mut state)
+        };
+        if finishing {
+            self.publish(Outcome::Succeeded);
+        }
+    }
+
+    /// Dispatches instructions triggering entry commands of the execution with `push`.
+    ///
+    /// Entries are counted against execution's limits and recorded as triggers if execution
+    /// is recorded. Execution fails once a limit is exceeded.
+    pub(crate) fn start<F>(
+        self: &Arc<Self>,
+        entries: &[CommandIndex],
+        tracer: &Tracer,
+        metrics: &Metrics,
+        push: F,
+    ) where
+        F: FnMut(Instruction),
+    {
+        let mut instructions = Vec::new();
+        for command in entries.iter() {
+            if let Err(e) = self.admit(0) {
+                self.fail(e);
+                break;
+            }
+            let instruction = Instruction::new(Arc::clone(self), *command, Values::new());
+            if let Some(recorder) = self.recorder() {
+                recorder.triggered(&instruction);
+            }
+            instructions.push(instruction);
+        }
+        self.dispatch(instructions, tracer, metrics, push);
+    }
+
+    /// Dispatches instructions of the execution with `push`, saving a checkpoint first if
+    /// the execution is checkpointed.
+    pub(crate) fn dispatch<F>(
+        &self,
+        mut instructions: Vec<Instruction>,
+        tracer: &Tracer,
+        metrics: &Metrics,
+        mut push: F,
+    ) where
+        F: FnMut(Instruction),
+    {
+        // Holds execution open until all instructions are dispatched.
+        self.dispatched();
+        for instruction in instructions.iter_mut() {
+            self.dispatched();
+            if let Some(checkpointer) = self.checkpointer() {
+                checkpointer.track(instruction);
+            }
+        }
+        if let Some(checkpointer) = self.checkpointer() {
+            if let Err(e) = checkpointer.save(self) {
+                self.fail(e);
+            }
+        }
+
+        for instruction in instructions {
+            tracer.record(self.id, || TraceEvent::dispatched(&instruction));
+            metrics.record(|| Sample::Dispatched(CommandLabels::new(&instruction)));
+            push(instruction);
+        }
+        self.completed();
+    }
+
+    /// Finishes execution with an error, unless it has already finished.
+    pub fn fail(&self, error: EngineError) {
+        self.finish(Outcome::Failed(error));
+    }
+
+    /// Cancels execution, unless it has already finished.
+    ///
+    /// Queued instructions of a cancelled execution are dropped and running handlers
+    /// observe cancellation through execution's token.
+    pub fn cancel(&self) {
+        self.token.cancel();
+        self.finish(Outcome::Cancelled);
+    }
+
+    /// Returns a future that resolves to execution's outcome once it finishes.
+    pub(crate) fn completion(self: &Arc<Self>) -> Completion {
+        Completion {
+            execution: Arc::clone(self),
+        }
+    }
+
+    /// Returns execution's cancellation token.
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    /// Returns whether execution has finished, or is about to publish its outcome.
+    pub fn is_finished(&self) -> bool {
+        self.lock().finishing
+    }
+
+    /// Returns the number of instructions queued or being handled.
+    pub fn in_flight(&self) -> usize {
+        self.lock().in_flight
+    }
+
+    /// Returns execution's outcome if it has finished.
+    pub fn outcome(&self) -> Option<Outcome> {
+        self.lock().outcome.clone()
+    }
+
+    /// Blocks until execution finishes or timeout elapses.
+    pub fn wait(&self, timeout: Option<Duration>) -> Option<Outcome> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut state = self.lock();
+        while state.outcome.is_none() {
+            state = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return None;
+                    }
+                    self.finished
+                        .wait_timeout(state, deadline - now)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0
+                }
+                None => self.finished.wait(state).unwrap_or_else(|e| e.into_inner()),
+            };
+        }
+        state.outcome.clone()
+    }
+
+    fn check_steps_locked(&self, state: &State, pending: usize) -> Result<(), EngineError> {
+        match self.limits().max_steps {
+            Some(max_steps) if state.steps + pending > max_steps => Err(EngineError::from(
+                format!("Execution {} exceeded max steps ({}).", self.id, max_steps),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn finish(&self, outcome: Outcome) {
+        if Self::start_finishing(&mut self.lock()) {
+            self.publish(outcome);
+        }
+    }
+
+    /// Marks execution as finishing, returning whether it wasn't already.
+    fn start_finishing(state: &mut State) -> bool {
+        !std::mem::replace(&mut state.finishing, true)
+    }
+
+    /// Removes execution's checkpoint and publishes its outcome. A failed removal fails
+    /// an otherwise succeeded execution, as it would be resumed again.
+    fn publish(&self, outcome: Outcome) {
+        let removed = match &self.checkpointer {
+            Some(checkpointer) => checkpointer.remove(self.id),
+            None => Ok(()),
+        };
+        let outcome = match (outcome, removed) {
+            (Outcome::Succeeded, Err(e)) => Outcome::Failed(e),
+            (outcome, _) => outcome,
+        };
+        let mut state = self.lock();
+        state.outcome = Some(outcome);
+        self.finished.notify_all();
+        for waker in state.wakers.drain(..) {
+            waker.wake();
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl ExecutionOptions {
+    /// Constructs default `ExecutionOptions`.
+    pub fn new() -> Self {
+        ExecutionOptions::default()
+    }
+
+    /// Records or replays execution with a recorder.
+    pub fn recorder(mut self, recorder: Arc<Recorder>) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Enforces limits on execution, overriding engine's defaults where set.
+    pub fn limits(mut self, limits: ExecutionLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Retries failed commands of a placed node with a policy in this execution only,
+    /// overriding the policy set on the node in its graph or its schema's policy.
+    pub fn retry_policy(mut self, key: &str, policy: RetryPolicy) -> Self {
+        self.retry_policies.insert(String::from(key), policy);
+        self
+    }
+
+    /// Schedules instructions of execution with a priority. Defaults to 0, the lowest.
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Returns options with unset limits taken from defaults.
+    pub(crate) fn inherit_limits(mut self, defaults: &ExecutionLimits) -> Self {
+        self.limits = self.limits.or(defaults);
+        self
+    }
+}
+
+impl ExecutionLimits {
+    /// Constructs `ExecutionLimits` with no limits set.
+    pub fn new() -> Self {
+        ExecutionLimits::default()
+    }
+
+    /// Returns limits with unset ones taken from defaults.
+    pub fn or(self, defaults: &ExecutionLimits) -> Self {
+        ExecutionLimits {
+            max_steps: self.max_steps.or(defaults.max_steps),
+            max_depth: self.max_depth.or(defaults.max_depth),
+            max_wall_time_ms: self.max_wall_time_ms.or(defaults.max_wall_time_ms),
+        }
+    }
+}
+
+impl CancellationToken {
+    /// Constructs a `CancellationToken`.
+    pub fn new() -> Self {
+        CancellationToken::default()
+    }
+
+    /// Requests cancellation.
+    pub fn cancel(&self) {
+        let (lock, cancelled) = &*self.signal;
+        let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+        self.cancelled.store(true, Ordering::SeqCst);
+        cancelled.notify_all();
+    }
+
+    /// Returns whether cancellation was requested.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Blocks until cancellation is requested or a timeout elapses. Returns whether
+    /// cancellation was requested.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let (lock, cancelled) = &*self.signal;
+        let mut guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            if self.is_cancelled() {
+                return true;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            guard = cancelled
+                .wait_timeout(guard, deadline - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+}
+
+impl ExecutionHandle {
+    /// Constructs an `ExecutionHandle`.
+    pub fn new(execution: Arc<Execution>, entries: Vec<CommandIndex>) -> Self {
+        ExecutionHandle { execution, entries }
+    }
+
+    /// Returns execution's id.
+    pub fn id(&self) -> ExecutionId {
+        self.execution.id()
+    }
+
+    /// Returns the plan being executed.
+    pub fn plan(&self) -> &Arc<ExecutionPlan> {
+        self.execution.plan()
+    }
+
+    /// Returns what has been recorded so far, if execution is recorded or replayed.
+    pub fn recording(&self) -> Option<Recording> {
+        self.execution
+            .recorder()
+            .map(|recorder| recorder.recording())
+    }
+
+    /// Returns commands that started the execution.
+    pub fn entries(&self) -> &[CommandIndex] {
+        &self.entries
+    }
+
+    /// Returns the number of instructions dispatched so far.
+    pub fn steps(&self) -> usize {
+        self.execution.steps()
+    }
+
+    /// Returns the number of instructions queued or being handled.
+    pub fn in_flight(&self) -> usize {
+        self.execution.in_flight()
+    }
+
+    /// Returns state stored by a node of the execution, empty if it stored none.
+    pub fn node_state(&self, key: &str) -> Values {
+        self.execution.node_state(key)
+    }
+
+    /// Returns whether execution has finished.
+    pub fn is_finished(&self) -> bool {
+        self.execution.is_finished()
+    }
+
+    /// Returns execution's current status without blocking.
+    pub fn status(&self) -> ExecutionStatus {
+        match self.execution.outcome() {
+            Some(outcome) => ExecutionStatus::Finished(outcome),
+            None => ExecutionStatus::Running,
+        }
+    }
+
+    /// Returns execution's outcome if it has finished.
+    pub fn outcome(&self) -> Option<Outcome> {
+        self.execution.outcome()
+    }
+
+    /// Cancels execution. Has no effect if execution has already finished.
+    pub fn cancel(&self) {
+        self.execution.cancel();
+    }
+
+    /// Blocks until execution finishes.
+    pub fn join(&self) -> Outcome {
+        self.execution.wait(None).unwrap()
+    }
+
+    /// Blocks until execution finishes or timeout elapses. Returns `None` on timeout.
+    pub fn join_timeout(&self, timeout: Duration) -> Option<Outcome> {
+        self.execution.wait(Some(timeout))
+    }
+
+    /// Returns a future that resolves once execution finishes, without blocking a thread.
+    pub fn completion(&self) -> Completion {
+        self.execution.completion()
+    }
+}
+
+impl Future for Completion {
+    type Output = Outcome;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Outcome> {
+        let mut state = self.execution.lock();
+        match &state.outcome {
+            Some(outcome) => Poll::Ready(outcome.clone()),
+            None => {
+                if !state.wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                    state.wakers.push(cx.waker().clone());
+                }
+                Poll::Pending
+            }
+        }
+    }
+}
@@ -0,0 +1,97 @@
+//! Conversions between values and JSON.
+
+use serde_json::json;
+
+use graph::value::Value;
+
+use crate::error::EngineError;
+use crate::library::Values;
+
+/// Converts a value to JSON.
+pub(crate) fn value_to_json(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Integer(value) => json!(value),
+        Value::Float(value) => json!(value),
+        Value::Boolean(value) => json!(value),
+        Value::String(value) => json!(value),
+    }
+}
+
+/// Converts JSON to a value. Whole numbers are read as integers.
+pub(crate) fn value_from_json(json: &serde_json::Value) -> Result<Value, EngineError> {
+    match json {
+        serde_json::Value::Bool(value) => Ok(Value::Boolean(*value)),
+        serde_json::Value::String(value) => Ok(Value::String(value.clone())),
+        serde_json::Value::Number(number) => match (number.as_i64(), number.as_f64()) {
+            (Some(value), _) => Ok(Value::Integer(value)),
+            (None, Some(value)) => Ok(Value::Float(value)),
+            _ => Err(EngineError::from(format!("Unsupported number {}.", number))),
+        },
+        _ => Err(EngineError::from(format!("Unsupported value {}.", json))),
+    }
+}
+
+/// Converts values to a JSON object.
+pub(crate) fn values_to_json(values: &Values) -> serde_json::Value {
+    serde_json::Value::Object(
+        values
+            .iter()
+            .map(|(id, value)| (id.clone(), value_to_json(value)))
+            .collect(),
+    )
+}
+
+/// Converts a JSON object to values.
+pub(crate) fn values_from_json(json: &serde_json::Value) -> Result<Values, EngineError> {
+    let object = json
+        .as_object()
+        .ok_or_else(|| EngineError::from(format!("Expected an object, found {}.", json)))?;
+    object
+        .iter()
+        .map(|(id, value)| Ok((id.clone(), value_from_json(value)?)))
+        .collect()
+}
+
+/// Returns an array field of a JSON object, naming `context` in the error if it is missing.
+pub(crate) fn get_array<'a>(
+    json: &'a serde_json::Value,
+    id: &str,
+    context: &str,
+) -> Result<&'a Vec<serde_json::Value>, EngineError> {
+    json[id].as_array().ok_or_else(|| missing(id, context))
+}
+
+/// Returns a string field of a JSON object, naming `context` in the error if it is missing.
+pub(crate) fn get_string(
+    json: &serde_json::Value,
+    id: &str,
+    context: &str,
+) -> Result<String, EngineError> {
+    json[id]
+        .as_str()
+        .map(String::from)
+        .ok_or_else(|| missing(id, context))
+}
+
+/// Returns an unsigned integer field of a JSON object, naming `context` in the error if it
+/// is missing.
+pub(crate) fn get_u64(
+    json: &serde_json::Value,
+    id: &str,
+    context: &str,
+) -> Result<u64, EngineError> {
+    json[id].as_u64().ok_or_else(|| missing(id, context))
+}
+
+fn missing(id: &str, context: &str) -> EngineError {
+    EngineError::from(format!("Invalid {}: missing '{}'.", context, id))
+}
+
+/// Returns a number field of a JSON object, naming `context` in the error if it is missing.
+pub(crate) fn get_f64(
+    json: &serde_json::Value,
+    id: &str,
+    context: &str,
+) -> Result<f64, EngineError> {
+    json[id].as_f64().ok_or_else(|| missing(id, context))
+}
@@ -1,5 +1,3 @@
-#![cfg_attr(feature = "clippy", feature(plugin))]
-#![cfg_attr(feature = "clippy", plugin(clippy))]
 #![warn(
     missing_docs,
     missing_copy_implementations,
@@ -14,45 +12,100 @@
 
 //! Engine for executing graph based programs.
 
-extern crate config;
-extern crate crossbeam;
-extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 
-use std::sync::Arc;
-use std::time::Duration;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
 use std::{env, thread};
 
 use config::{Config, File};
-use crossbeam::channel::{Receiver, Sender};
 
 use graph::graph::Graph;
 
+use crate::checkpoint::{CheckpointStore, Checkpointer};
 use crate::error::EngineError;
-use crate::library::Library;
-use crate::message::{Context, Message};
+use crate::execution::{
+    Execution, ExecutionHandle, ExecutionId, ExecutionLimits, ExecutionOptions, Outcome,
+};
+use crate::library::{Library, Values};
+use crate::message::Instruction;
+use crate::metrics::{Metrics, MetricsSink};
+use crate::plan::{CommandIndex, ExecutionPlan};
+use crate::resolver::Resolver;
+use crate::scheduler::{Scheduler, SchedulerConfig};
+use crate::trace::{TraceSink, Tracer};
 use crate::worker::Worker;
 
+pub mod checkpoint;
+pub mod debugger;
+pub mod dispatcher;
 pub mod error;
+pub mod execution;
+mod json;
 pub mod library;
+pub mod local;
 pub mod message;
+pub mod metrics;
+pub mod optimizer;
+pub mod plan;
 pub mod processor;
+pub mod record;
+pub mod resolver;
+pub mod retry;
+#[cfg(feature = "tokio")]
+pub mod runtime;
+pub mod scheduler;
+pub mod trace;
 pub mod worker;
 
-#[derive(Debug, Deserialize)]
+/// Configuration of workers.
+#[derive(Debug, Clone, Copy, Deserialize)]
 pub struct WorkerConfig {
     pool_size: usize,
+    #[serde(default)]
+    restart: RestartPolicy,
 }
 
-#[derive(Debug, Deserialize)]
+/// Decides what happens to a worker that panics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    /// Worker thread terminates.
+    Never,
+    /// Worker is restarted on the same thread.
+    #[default]
+    OnPanic,
+}
+
+/// Decides what happens to running executions when an engine shuts down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownMode {
+    /// Waits for running executions to finish.
+    Drain,
+    /// Cancels running executions.
+    Abort,
+}
+
+/// Configuration of an engine.
+#[derive(Debug, Clone, Copy, Deserialize)]
 pub struct EngineConfig {
+    /// Worker configuration.
     pub worker: WorkerConfig,
+    /// Limits of executions that don't override them.
+    #[serde(default)]
+    pub limits: ExecutionLimits,
+    /// Scheduling of instructions between executions.
+    #[serde(default)]
+    pub scheduler: SchedulerConfig,
 }
 
 static DEFAULT_CONFIG_PATH: &str = "config.yaml";
 
 impl EngineConfig {
+    /// Loads config from default path, merged with file at `APP_CONFIG_PATH` if set.
     pub fn load() -> Result<EngineConfig, EngineError> {
         let mut config = Config::new();
         config.merge(File::with_name(DEFAULT_CONFIG_PATH))?;
@@ -75,48 +128,344 @@ pub struct Engine {
     /// Library used by this engine.
     pub library: Arc<Library>,
 
-    message_sender: Sender<Message>,
-    message_receiver: Receiver<Message>,
+    scheduler: Arc<Scheduler>,
+    next_execution_id: AtomicU64,
+    executions: Mutex<Vec<ExecutionHandle>>,
+    workers: Vec<JoinHandle<()>>,
+    stopped: bool,
+    tracer: Tracer,
+    metrics: Metrics,
+    checkpoints: Option<Arc<dyn CheckpointStore>>,
+    checkpointed_from: ExecutionId,
 }
 
 impl Engine {
     /// Constructs a new `Engine`.
     pub fn new(config: EngineConfig, library: Library) -> Self {
-        let (s, r) = crossbeam::channel::bounded::<Message>(10);
         Engine {
+            scheduler: Arc::new(Scheduler::new(config.scheduler)),
             config,
             library: Arc::new(library),
-            message_sender: s,
-            message_receiver: r,
+            next_execution_id: AtomicU64::new(1),
+            executions: Mutex::new(Vec::new()),
+            workers: Vec::new(),
+            stopped: false,
+            tracer: Tracer::default(),
+            metrics: Metrics::default(),
+            checkpoints: None,
+            checkpointed_from: 0,
         }
     }
 
+    /// Records trace of executions to a sink.
+    ///
+    /// Only workers started after this call trace instructions they handle.
+    pub fn set_trace_sink(&mut self, sink: Arc<dyn TraceSink>) {
+        self.tracer = Tracer::new(sink);
+    }
+
     /// Run the engine.
+    ///
+    /// Workers that panic are restarted according to `RestartPolicy`.
     pub fn run(&mut self) {
-        for i in 0..self.config.worker.pool_size {
+        self.stopped = false;
+        let first_id = self.workers.len();
+        for i in first_id..first_id + self.config.worker.pool_size {
             let id = i as u64;
-            let outbox = self.message_sender.clone();
-            let inbox = self.message_receiver.clone();
+            let scheduler = Arc::clone(&self.scheduler);
             let library = Arc::downgrade(&self.library);
+            let restart = self.config.worker.restart;
+            let tracer = self.tracer.clone();
+            let metrics = self.metrics.clone();
 
-            thread::spawn(move || {
-                let mut worker = Worker::new(id, outbox, inbox, library);
-                worker.run();
+            self.workers.push(thread::spawn(move || {
+                let mut worker = Worker::new(id, scheduler, library, tracer, metrics);
+                loop {
+                    match panic::catch_unwind(AssertUnwindSafe(|| worker.run())) {
+                        Ok(()) => break,
+                        Err(e) if restart == RestartPolicy::Never => panic::resume_unwind(e),
+                        // Panics of handlers are traced by the worker before unwinding.
+                        Err(_) => {}
+                    }
+                }
+            }));
+        }
+        self.scheduler.set_workers(self.workers.len());
+    }
+
+    /// Stops workers and waits for their threads to finish.
+    ///
+    /// With `ShutdownMode::Drain` running executions are waited for, unless the engine
+    /// has no workers to finish them, in which case they are cancelled as with
+    /// `ShutdownMode::Abort`. No executions can be started until the engine is ran again.
+    pub fn shutdown(&mut self, mode: ShutdownMode) -> Result<(), EngineError> {
+        self.stopped = true;
+        let executions = std::mem::take(&mut *self.lock_executions());
+        for execution in executions.iter() {
+            match mode {
+                ShutdownMode::Drain if !self.workers.is_empty() => {
+                    execution.join();
+                }
+                _ => execution.cancel(),
+            }
+        }
+
+        // Workers terminated by panic can't take their stop, which would otherwise stop
+        // workers of the next run.
+        let running = self.workers.iter().filter(|w| !w.is_finished()).count();
+        self.scheduler.set_workers(0);
+        self.scheduler.stop(running);
+        let panicked = self
+            .workers
+            .drain(..)
+            .map(JoinHandle::join)
+            .filter(Result::is_err)
+            .count();
+        self.scheduler.clear_stops();
+        if panicked > 0 {
+            return Err(EngineError::from(format!(
+                "{} worker(s) terminated by panic.",
+                panicked
+            )));
+        }
+        Ok(())
+    }
+
+    /// Records metrics of executions to a sink.
+    ///
+    /// Only workers started after this call record metrics of instructions they handle.
+    pub fn set_metrics_sink(&mut self, sink: Arc<dyn MetricsSink>) {
+        self.metrics = Metrics::new(sink);
+    }
+
+    /// Saves checkpoints of executions started after this call to a store, so that
+    /// they can be resumed with `resume` if the engine stops before they finish.
+    ///
+    /// Executions started afterwards get ids past those of executions checkpointed in the
+    /// store, so their checkpoints never replace ones left to resume.
+    pub fn set_checkpoint_store(
+        &mut self,
+        store: Arc<dyn CheckpointStore>,
+    ) -> Result<(), EngineError> {
+        if let Some(last) = store.executions()?.into_iter().max() {
+            self.next_execution_id.fetch_max(last + 1, Ordering::SeqCst);
+        }
+        self.checkpointed_from = self.next_execution_id.load(Ordering::SeqCst);
+        self.checkpoints = Some(store);
+        Ok(())
+    }
+
+    /// Resumes executions left unfinished in the checkpoint store, e.g. by a process that
+    /// crashed, and returns their handles.
+    ///
+    /// Executions keep their ids, limits, retry policies and priorities, and the time they
+    /// ran for counts against their max wall time. Recorders are not restored. Pending
+    /// instructions are dispatched again, so commands that were being handled when a
+    /// checkpoint was saved run once more. Executions started on this engine are left
+    /// alone. An engine must be ran first.
+    pub fn resume(&self) -> Result<Vec<ExecutionHandle>, EngineError> {
+        if self.stopped {
+            return Err(EngineError::new("Engine is shut down."));
+        }
+        let store = self
+            .checkpoints
+            .as_ref()
+            .ok_or_else(|| EngineError::new("Engine has no checkpoint store."))?;
+
+        let mut handles = Vec::new();
+        for checkpoint in store.load()? {
+            if checkpoint.execution >= self.checkpointed_from {
+                continue;
+            }
+            let graph = store.load_graph(checkpoint.execution, &self.library.schema)?;
+            let plan = Arc::new(ExecutionPlan::compile(&graph, &self.library)?);
+            let entries = checkpoint
+                .entries
+                .iter()
+                .map(|(node, command)| plan.find_command(node, command))
+                .collect::<Result<Vec<_>, _>>()?;
+            let pending = checkpoint
+                .pending
+                .iter()
+                .map(|pending| Ok((plan.find_command(&pending.node, &pending.command)?, pending)))
+                .collect::<Result<Vec<_>, EngineError>>()?;
+
+            let id = checkpoint.execution;
+            self.next_execution_id.fetch_max(id + 1, Ordering::SeqCst);
+            let options = checkpoint.retry_policies.into_iter().fold(
+                ExecutionOptions::new()
+                    .limits(checkpoint.limits)
+                    .priority(checkpoint.priority),
+                |options, (key, policy)| options.retry_policy(&key, policy),
+            );
+            let options = options.inherit_limits(&self.config.limits);
+            let checkpointer = Checkpointer::new(Arc::clone(store), checkpoint.entries);
+            let execution = Arc::new(
+                Execution::with_options(id, Arc::clone(&plan), options)
+                    .with_elapsed(checkpoint.elapsed)
+                    .with_checkpointer(checkpointer),
+            );
+            execution.restore_steps(checkpoint.steps);
+            execution.restore_node_states(checkpoint.states);
+            let handle = self.register(Arc::clone(&execution), entries);
+
+            let instructions = pending
+                .into_iter()
+                .map(|(command, pending)| {
+                    let instruction =
+                        Instruction::new(Arc::clone(&execution), command, pending.payload.clone())
+                            .at_depth(pending.depth);
+                    match &pending.edge {
+                        Some(edge) => instruction.via(edge),
+                        None => instruction,
+                    }
+                })
+                .collect();
+            execution.dispatch(instructions, &self.tracer, &self.metrics, |instruction| {
+                self.scheduler.push(instruction)
             });
+            handles.push(handle);
         }
+        Ok(handles)
     }
 
-    /// Execute a graph on this engine. An engine must be ran first.
-    pub fn execute(&self, graph: Graph) {
-        let graph = Arc::new(graph);
-        let s2 = self.message_sender.clone();
-        thread::spawn(move || loop {
-            thread::sleep(Duration::from_secs(1));
+    /// Runs a graph with arguments bound to its parameters and returns values of its results
+    /// once execution completes.
+    ///
+    /// Every `action` node of the graph is triggered once. An engine must be ran first.
+    pub fn call(&self, mut graph: Graph, arguments: &Values) -> Result<Values, EngineError> {
+        graph.bind(arguments)?;
+        let handle = self.execute_actions(graph)?;
+        match handle.join() {
+            Outcome::Succeeded => {}
+            Outcome::Failed(e) => return Err(e),
+            Outcome::Cancelled => return Err(EngineError::new("Execution was cancelled.")),
+        }
 
-            let context = Context::new(Arc::clone(&graph), graph.get_node("a1"));
-            s2.send(Message::instruction(context, "trigger"));
+        let plan = handle.plan();
+        let mut resolver = Resolver::new(plan);
+        let mut results = Values::new();
+        for (id, slot) in plan.results.iter() {
+            results.insert(id.clone(), resolver.resolve(*slot)?);
+        }
+        Ok(results)
+    }
+
+    /// Starts executing a graph by triggering a command of a node.
+    ///
+    /// Returns as soon as the command is dispatched. An engine must be ran first.
+    pub fn execute(
+        &self,
+        graph: Graph,
+        key: &str,
+        command_id: &str,
+    ) -> Result<ExecutionHandle, EngineError> {
+        self.execute_with(graph, key, command_id, ExecutionOptions::default())
+    }
+
+    /// Starts executing a graph by triggering a command of a node with options.
+    pub fn execute_with(
+        &self,
+        graph: Graph,
+        key: &str,
+        command_id: &str,
+        options: ExecutionOptions,
+    ) -> Result<ExecutionHandle, EngineError> {
+        let plan = Arc::new(ExecutionPlan::compile(&graph, &self.library)?);
+        let command = plan.find_command(key, command_id)?;
+        self.start(graph, plan, vec![command], options)
+    }
+
+    /// Starts executing a graph by triggering every `action` node once.
+    ///
+    /// Returns as soon as commands are dispatched. An engine must be ran first.
+    pub fn execute_actions(&self, graph: Graph) -> Result<ExecutionHandle, EngineError> {
+        self.execute_actions_with(graph, ExecutionOptions::default())
+    }
+
+    /// Starts executing a graph by triggering every `action` node once with options.
+    pub fn execute_actions_with(
+        &self,
+        graph: Graph,
+        options: ExecutionOptions,
+    ) -> Result<ExecutionHandle, EngineError> {
+        let plan = Arc::new(ExecutionPlan::compile(&graph, &self.library)?);
+        let commands = plan.get_actions();
+        self.start(graph, plan, commands, options)
+    }
+
+    fn start(
+        &self,
+        graph: Graph,
+        plan: Arc<ExecutionPlan>,
+        entries: Vec<CommandIndex>,
+        options: ExecutionOptions,
+    ) -> Result<ExecutionHandle, EngineError> {
+        if self.stopped {
+            return Err(EngineError::new("Engine is shut down."));
+        }
+        plan.check_entries(&entries)?;
+        self.scheduler.check_capacity(entries.len())?;
+        let id = self.next_execution_id.fetch_add(1, Ordering::SeqCst);
+        let options = options.inherit_limits(&self.config.limits);
+        let mut execution = Execution::with_options(id, Arc::clone(&plan), options);
+        if let Some(store) = &self.checkpoints {
+            store.save_graph(id, &graph)?;
+            let entries = entries
+                .iter()
+                .map(|command| {
+                    let command = &plan.commands[*command];
+                    (
+                        plan.nodes[command.node].key.clone(),
+                        command.reference.property.id.clone(),
+                    )
+                })
+                .collect();
+            execution = execution.with_checkpointer(Checkpointer::new(Arc::clone(store), entries));
+        }
+        let execution = Arc::new(execution);
+        let handle = self.register(Arc::clone(&execution), entries.clone());
+
+        execution.start(&entries, &self.tracer, &self.metrics, |instruction| {
+            self.scheduler.push(instruction)
         });
 
-        thread::sleep(Duration::from_secs(10));
+        Ok(handle)
+    }
+
+    /// Returns handles of executions that have not finished yet, in order they were started.
+    pub fn executions(&self) -> Vec<ExecutionHandle> {
+        let mut executions = self.lock_executions();
+        executions.retain(|execution| !execution.is_finished());
+        executions.clone()
+    }
+
+    /// Returns a handle of an execution that has not finished yet.
+    pub fn execution(&self, id: ExecutionId) -> Option<ExecutionHandle> {
+        self.lock_executions()
+            .iter()
+            .find(|execution| execution.id() == id && !execution.is_finished())
+            .cloned()
+    }
+
+    fn register(&self, execution: Arc<Execution>, entries: Vec<CommandIndex>) -> ExecutionHandle {
+        let handle = ExecutionHandle::new(execution, entries);
+        let mut executions = self.lock_executions();
+        executions.retain(|execution| !execution.is_finished());
+        executions.push(handle.clone());
+        handle
+    }
+
+    /// Queues instructions of an execution for workers, saving a checkpoint first if the
+    /// execution is checkpointed.
+    fn lock_executions(&self) -> MutexGuard<'_, Vec<ExecutionHandle>> {
+        self.executions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        // The only error is workers terminated by panic, which they have already traced.
+        let _ = self.shutdown(ShutdownMode::Abort);
     }
 }
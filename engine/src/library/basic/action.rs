@@ -1,12 +1,18 @@
+//! Action node is an entry point that can be triggered from outside of a graph.
+
 use graph::schema::node::Node;
 
+/// Node id.
 pub const ID: &str = "action";
+/// Triggers the action.
 pub const COMMAND_TRIGGER: &str = "trigger";
+/// Fired when action is triggered.
 pub const EVENT_TRIGGERED: &str = "triggered";
 
+/// Returns node's schema.
 pub fn get() -> Node {
-    Node::builder(&ID)
-        .command(&COMMAND_TRIGGER)
-        .event(&EVENT_TRIGGERED)
+    Node::builder(ID)
+        .command(COMMAND_TRIGGER)
+        .event(EVENT_TRIGGERED)
         .build()
 }
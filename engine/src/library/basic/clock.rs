@@ -0,0 +1,32 @@
+//! Clock node provides current time. It is not deterministic.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use graph::schema::node::Node;
+use graph::value::{DataType, Value};
+
+use crate::error::EngineError;
+use crate::library::Values;
+
+/// Node id.
+pub const ID: &str = "clock";
+/// Milliseconds since Unix epoch.
+pub const OUTPUT_NOW: &str = "now";
+
+/// Returns node's schema.
+pub fn get() -> Node {
+    Node::builder(ID)
+        .output(OUTPUT_NOW, DataType::Integer)
+        .build()
+}
+
+/// Returns current time.
+pub fn evaluate(_inputs: &Values) -> Result<Values, EngineError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| EngineError::from(format!("Clock is before Unix epoch: {}", e)))?;
+
+    let mut outputs = Values::new();
+    outputs.insert(OUTPUT_NOW.into(), Value::from(now.as_millis() as i64));
+    Ok(outputs)
+}
@@ -1,13 +1,31 @@
+//! Integer node provides a constant integer value.
+
 use graph::schema::node::Node;
-use graph::value::DataType;
+use graph::value::{DataType, Value};
+
+use crate::error::EngineError;
+use crate::library::{get_integer, Values};
 
+/// Node id.
 pub const ID: &str = "integer";
+/// Provided value.
 pub const INPUT_VALUE: &str = "value";
+/// Returns provided value.
 pub const OUTPUT_RETURN_VALUE: &str = "return-value";
 
+/// Returns node's schema.
 pub fn get() -> Node {
-    Node::builder(&ID)
-        .input(&INPUT_VALUE, DataType::Integer)
-        .output(&OUTPUT_RETURN_VALUE, DataType::Integer)
+    Node::builder(ID)
+        .input(INPUT_VALUE, DataType::Integer)
+        .output(OUTPUT_RETURN_VALUE, DataType::Integer)
         .build()
 }
+
+/// Returns provided value.
+pub fn evaluate(inputs: &Values) -> Result<Values, EngineError> {
+    let value = get_integer(inputs, INPUT_VALUE)?;
+
+    let mut outputs = Values::new();
+    outputs.insert(OUTPUT_RETURN_VALUE.into(), Value::from(value));
+    Ok(outputs)
+}
@@ -1,15 +1,38 @@
+//! Minus node subtracts two integers.
+
 use graph::schema::node::Node;
-use graph::value::DataType;
+use graph::value::{DataType, Value};
+
+use crate::error::EngineError;
+use crate::library::{get_integer, Values};
 
+/// Node id.
 pub const ID: &str = "minus";
+/// Minuend.
 pub const INPUT_A: &str = "a";
+/// Subtrahend.
 pub const INPUT_B: &str = "b";
+/// Difference of operands.
 pub const OUTPUT_C: &str = "c";
 
+/// Returns node's schema.
 pub fn get() -> Node {
-    Node::builder(&ID)
-        .input(&INPUT_A, DataType::Integer)
-        .input(&INPUT_B, DataType::Integer)
-        .output(&OUTPUT_C, DataType::Integer)
+    Node::builder(ID)
+        .input(INPUT_A, DataType::Integer)
+        .input(INPUT_B, DataType::Integer)
+        .output(OUTPUT_C, DataType::Integer)
         .build()
 }
+
+/// Computes `c = a - b`.
+pub fn evaluate(inputs: &Values) -> Result<Values, EngineError> {
+    let a = get_integer(inputs, INPUT_A)?;
+    let b = get_integer(inputs, INPUT_B)?;
+    let c = a
+        .checked_sub(b)
+        .ok_or_else(|| EngineError::from(format!("Integer overflow in {} - {}", a, b)))?;
+
+    let mut outputs = Values::new();
+    outputs.insert(OUTPUT_C.into(), Value::from(c));
+    Ok(outputs)
+}
@@ -1,4 +1,7 @@
+//! Basic nodes.
+
 pub mod action;
+pub mod clock;
 pub mod integer;
 pub mod minus;
 pub mod plus;
@@ -1,15 +1,38 @@
+//! Plus node adds two integers.
+
 use graph::schema::node::Node;
-use graph::value::DataType;
+use graph::value::{DataType, Value};
+
+use crate::error::EngineError;
+use crate::library::{get_integer, Values};
 
+/// Node id.
 pub const ID: &str = "plus";
+/// First operand.
 pub const INPUT_A: &str = "a";
+/// Second operand.
 pub const INPUT_B: &str = "b";
+/// Sum of operands.
 pub const OUTPUT_C: &str = "c";
 
+/// Returns node's schema.
 pub fn get() -> Node {
-    Node::builder(&ID)
-        .input(&INPUT_A, DataType::Integer)
-        .input(&INPUT_B, DataType::Integer)
-        .output(&OUTPUT_C, DataType::Integer)
+    Node::builder(ID)
+        .input(INPUT_A, DataType::Integer)
+        .input(INPUT_B, DataType::Integer)
+        .output(OUTPUT_C, DataType::Integer)
         .build()
 }
+
+/// Computes `c = a + b`.
+pub fn evaluate(inputs: &Values) -> Result<Values, EngineError> {
+    let a = get_integer(inputs, INPUT_A)?;
+    let b = get_integer(inputs, INPUT_B)?;
+    let c = a
+        .checked_add(b)
+        .ok_or_else(|| EngineError::from(format!("Integer overflow in {} + {}", a, b)))?;
+
+    let mut outputs = Values::new();
+    outputs.insert(OUTPUT_C.into(), Value::from(c));
+    Ok(outputs)
+}
@@ -1,13 +1,22 @@
+//! Printer node prints its content to standard output.
+
 use graph::schema::node::Node;
 use graph::value::DataType;
 
+/// Node id.
 pub const ID: &str = "printer";
+/// Prints content.
 pub const COMMAND_PRINT: &str = "print";
+/// Fired when printing fails.
+pub const EVENT_FAILED: &str = "failed";
+/// Content to print.
 pub const INPUT_CONTENT: &str = "content";
 
+/// Returns node's schema.
 pub fn get() -> Node {
-    Node::builder(&ID)
-        .command(&COMMAND_PRINT)
-        .input(&INPUT_CONTENT, DataType::Integer)
+    Node::builder(ID)
+        .command(COMMAND_PRINT)
+        .error_event(EVENT_FAILED)
+        .input(INPUT_CONTENT, DataType::Integer)
         .build()
 }
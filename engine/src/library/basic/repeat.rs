@@ -1,15 +1,47 @@
+//! Repeat node fires an event multiple times.
+
 use graph::schema::node::Node;
+use graph::schema::property::Field;
 use graph::value::DataType;
 
+/// Node id.
 pub const ID: &str = "repeat";
+/// Starts repeating.
 pub const COMMAND_START: &str = "start";
+/// Fired once for each repetition.
 pub const EVENT_EXECUTED: &str = "executed";
+/// Zero-based index of a repetition, carried by `executed`.
+pub const FIELD_ITERATION: &str = "iteration";
+/// State counting repetitions of the node within an execution.
+pub const STATE_EXECUTED: &str = "executed";
+/// Number of repetitions.
 pub const INPUT_TIMES: &str = "times";
+/// Most repetitions fired by one handled instruction. Further repetitions continue in
+/// following instructions.
+pub const BATCH_SIZE: i64 = 256;
+/// Payload field of a continuation with the id of the loop it continues.
+pub const FIELD_LOOP: &str = "repeat:loop";
+/// State counting loops started by the node within an execution.
+pub const STATE_LOOPS: &str = "loops";
+
+/// Returns key of the state with the index of the next repetition of a loop.
+pub fn state_next(id: i64) -> String {
+    format!("next:{}", id)
+}
+
+/// Returns key of the state with the number of repetitions of a loop.
+pub fn state_times(id: i64) -> String {
+    format!("times:{}", id)
+}
 
+/// Returns node's schema.
 pub fn get() -> Node {
-    Node::builder(&ID)
-        .command(&COMMAND_START)
-        .event(&EVENT_EXECUTED)
-        .input(&INPUT_TIMES, DataType::Integer)
+    Node::builder(ID)
+        .command(COMMAND_START)
+        .event_with_payload(
+            EVENT_EXECUTED,
+            &[Field::new(FIELD_ITERATION, DataType::Integer)],
+        )
+        .input(INPUT_TIMES, DataType::Integer)
         .build()
 }
@@ -1,64 +1,167 @@
-//! Contains schema utils.
+//! Nodes supported by the engine.
+
+use std::collections::{HashMap, HashSet};
 
 use graph::schema::node::Node;
 use graph::schema::property::{
     CommandProperty, EventProperty, InputProperty, OutputProperty, Property,
 };
 use graph::schema::Schema;
+use graph::value::Value;
 
 use crate::error::EngineError;
+use crate::retry::RetryPolicy;
 
 pub mod basic;
 
+/// Values by property ids.
+pub type Values = HashMap<String, Value>;
+
+/// Key of a command, unique among commands of a library and dense, so that handlers can be
+/// looked up by it without hashing.
+pub type CommandKey = usize;
+
+/// Computes outputs of a pure node from its inputs.
+pub type Function = fn(&Values) -> Result<Values, EngineError>;
+
+/// Collection of nodes supported by the engine.
 pub struct Library {
+    /// Schema of all nodes.
     pub schema: Schema,
+    functions: HashMap<String, Function>,
+    nondeterministic: HashSet<String>,
+    retry_policies: HashMap<String, RetryPolicy>,
+    command_keys: HashMap<(String, String), CommandKey>,
 }
 
+/// Reference to a node's command.
+#[derive(Debug, Clone)]
 pub struct CommandReference {
+    /// Node declaring the command.
     pub node: Node,
+    /// Referenced property.
     pub property: CommandProperty,
+    /// Key of the command in its library.
+    pub key: CommandKey,
 }
 
+/// Reference to a node's event.
+#[derive(Debug, Clone)]
 pub struct EventReference {
+    /// Node declaring the event.
     pub node: Node,
+    /// Referenced property.
     pub property: EventProperty,
 }
 
+/// Reference to a node's input.
+#[derive(Debug, Clone)]
 pub struct InputReference {
+    /// Node declaring the input.
     pub node: Node,
+    /// Referenced property.
     pub property: InputProperty,
 }
 
+/// Reference to a node's output.
+#[derive(Debug, Clone)]
 pub struct OutputReference {
+    /// Node declaring the output.
     pub node: Node,
+    /// Referenced property.
     pub property: OutputProperty,
 }
 
 impl Library {
-    /// Returns a `Schema` supported by current implementation of the engine.
+    /// Returns a `Library` supported by current implementation of the engine.
     pub fn get() -> Self {
         let schema = Schema::builder()
             .node(basic::action::get())
+            .node(basic::clock::get())
             .node(basic::integer::get())
             .node(basic::minus::get())
             .node(basic::plus::get())
             .node(basic::printer::get())
             .node(basic::repeat::get())
             .build();
-        Library { schema }
+        let mut functions: HashMap<String, Function> = HashMap::new();
+        functions.insert(basic::clock::ID.into(), basic::clock::evaluate);
+        functions.insert(basic::integer::ID.into(), basic::integer::evaluate);
+        functions.insert(basic::minus::ID.into(), basic::minus::evaluate);
+        functions.insert(basic::plus::ID.into(), basic::plus::evaluate);
+        let mut nondeterministic = HashSet::new();
+        nondeterministic.insert(basic::clock::ID.into());
+        let mut commands: Vec<(String, String)> = schema
+            .nodes
+            .values()
+            .flat_map(|node| {
+                node.properties
+                    .values()
+                    .filter(|property| property.is_command())
+                    .map(move |property| (node.id.clone(), property.id().clone()))
+            })
+            .collect();
+        commands.sort();
+        let command_keys = commands.into_iter().zip(0..).collect();
+        Library {
+            schema,
+            functions,
+            nondeterministic,
+            retry_policies: HashMap::new(),
+            command_keys,
+        }
+    }
+
+    /// Returns a function computing outputs of a node, if node has one.
+    pub fn get_function(&self, node_id: &str) -> Option<Function> {
+        self.functions.get(node_id).copied()
+    }
+
+    /// Returns whether a node only computes outputs from inputs and has no commands or events.
+    pub fn is_pure(&self, node: &Node) -> bool {
+        self.functions.contains_key(&node.id)
+            && node.properties.values().all(|property| property.is_data())
+    }
+
+    /// Returns whether a node's outputs only depend on its inputs.
+    ///
+    /// Outputs of non-deterministic nodes are never folded and are captured when recording.
+    pub fn is_deterministic(&self, node_id: &str) -> bool {
+        !self.nondeterministic.contains(node_id)
     }
 
+    /// Retries failed commands of a node with a policy, unless a placed node overrides it.
+    pub fn set_retry_policy(&mut self, node_id: &str, policy: RetryPolicy) {
+        self.retry_policies.insert(String::from(node_id), policy);
+    }
+
+    /// Returns a policy for retrying failed commands of a node, if it has one.
+    pub fn get_retry_policy(&self, node_id: &str) -> Option<&RetryPolicy> {
+        self.retry_policies.get(node_id)
+    }
+
+    /// Returns a reference to a node's command.
     pub fn get_command(
         &self,
         node_id: &str,
         command_id: &str,
     ) -> Result<CommandReference, EngineError> {
-        let node = self.schema.nodes.get(node_id).unwrap();
-        let property = node.properties.get(command_id).unwrap();
+        let (node, property) = self.get_property(node_id, command_id)?;
         if let Property::Command(property) = property {
+            let key = self
+                .command_keys
+                .get(&(node.id.clone(), property.id.clone()))
+                .copied()
+                .ok_or_else(|| {
+                    EngineError::from(format!(
+                        "Command '{}#{}' was added after the library was built.",
+                        node_id, command_id
+                    ))
+                })?;
             Ok(CommandReference {
                 node: node.clone(),
                 property: property.clone(),
+                key,
             })
         } else {
             Err(EngineError::from(format!(
@@ -68,9 +171,9 @@ impl Library {
         }
     }
 
+    /// Returns a reference to a node's event.
     pub fn get_event(&self, node_id: &str, event_id: &str) -> Result<EventReference, EngineError> {
-        let node = self.schema.nodes.get(node_id).unwrap();
-        let property = node.properties.get(event_id).unwrap();
+        let (node, property) = self.get_property(node_id, event_id)?;
         if let Property::Event(property) = property {
             Ok(EventReference {
                 node: node.clone(),
@@ -84,9 +187,9 @@ impl Library {
         }
     }
 
+    /// Returns a reference to a node's input.
     pub fn get_input(&self, node_id: &str, input_id: &str) -> Result<InputReference, EngineError> {
-        let node = self.schema.nodes.get(node_id).unwrap();
-        let property = node.properties.get(input_id).unwrap();
+        let (node, property) = self.get_property(node_id, input_id)?;
         if let Property::Input(property) = property {
             Ok(InputReference {
                 node: node.clone(),
@@ -100,13 +203,13 @@ impl Library {
         }
     }
 
+    /// Returns a reference to a node's output.
     pub fn get_output(
         &self,
         node_id: &str,
         output_id: &str,
     ) -> Result<OutputReference, EngineError> {
-        let node = self.schema.nodes.get(node_id).unwrap();
-        let property = node.properties.get(output_id).unwrap();
+        let (node, property) = self.get_property(node_id, output_id)?;
         if let Property::Output(property) = property {
             Ok(OutputReference {
                 node: node.clone(),
@@ -119,4 +222,35 @@ impl Library {
             )))
         }
     }
+
+    fn get_property(
+        &self,
+        node_id: &str,
+        property_id: &str,
+    ) -> Result<(&Node, &Property), EngineError> {
+        let node = self
+            .schema
+            .nodes
+            .get(node_id)
+            .ok_or_else(|| EngineError::from(format!("Node '{}' not found.", node_id)))?;
+        let property = node.properties.get(property_id).ok_or_else(|| {
+            EngineError::from(format!(
+                "Property '{}' not found for '{}'.",
+                property_id, node_id
+            ))
+        })?;
+        Ok((node, property))
+    }
+}
+
+/// Returns an integer value from values.
+pub fn get_integer(values: &Values, id: &str) -> Result<i64, EngineError> {
+    match values.get(id) {
+        Some(Value::Integer(value)) => Ok(*value),
+        Some(value) => Err(EngineError::from(format!(
+            "Value '{}' is not an integer: {:?}",
+            id, value
+        ))),
+        None => Err(EngineError::from(format!("Value '{}' is missing.", id))),
+    }
 }
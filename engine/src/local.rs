@@ -0,0 +1,224 @@
+//! Single-threaded executor that runs instructions on the current thread.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Instant;
+
+use graph::graph::Graph;
+
+use crate::dispatcher::Dispatcher;
+use crate::error::EngineError;
+use crate::execution::{Execution, ExecutionHandle, ExecutionId, ExecutionOptions};
+use crate::library::Library;
+use crate::message::Instruction;
+use crate::metrics::{Metrics, MetricsSink, Sample};
+use crate::plan::{CommandIndex, ExecutionPlan};
+use crate::processor::Processor;
+use crate::record::{Recorder, Recording};
+use crate::trace::{TraceSink, Tracer};
+
+/// Runs graphs with the same processors as an `Engine`, but on the calling thread.
+///
+/// Instructions are handled one at a time in the order they were dispatched, so runs
+/// are deterministic.
+pub struct LocalExecutor {
+    library: Arc<Library>,
+    dispatcher: Dispatcher,
+    queue: VecDeque<Instruction>,
+    next_execution_id: ExecutionId,
+}
+
+impl LocalExecutor {
+    /// Constructs a `LocalExecutor` with processors of the basic library.
+    pub fn new(library: Arc<Library>) -> Result<Self, EngineError> {
+        let dispatcher = Dispatcher::new(&library)?;
+        Ok(LocalExecutor::with_dispatcher(library, dispatcher))
+    }
+
+    /// Constructs a `LocalExecutor` with given processors.
+    pub fn with_processors(library: Arc<Library>, processors: Vec<Box<dyn Processor>>) -> Self {
+        LocalExecutor::with_dispatcher(library, Dispatcher::with_processors(processors))
+    }
+
+    fn with_dispatcher(library: Arc<Library>, dispatcher: Dispatcher) -> Self {
+        LocalExecutor {
+            library,
+            dispatcher,
+            queue: VecDeque::new(),
+            next_execution_id: 1,
+        }
+    }
+
+    /// Records trace of executions to a sink.
+    pub fn set_trace_sink(&mut self, sink: Arc<dyn TraceSink>) {
+        self.dispatcher.set_tracer(Tracer::new(sink));
+    }
+
+    /// Records metrics of executions to a sink.
+    pub fn set_metrics_sink(&mut self, sink: Arc<dyn MetricsSink>) {
+        self.dispatcher.set_metrics(Metrics::new(sink));
+    }
+
+    /// Queues a command of a node to start executing a graph.
+    ///
+    /// Nothing is handled until the executor is stepped or ran.
+    pub fn execute(
+        &mut self,
+        graph: Graph,
+        key: &str,
+        command_id: &str,
+    ) -> Result<ExecutionHandle, EngineError> {
+        self.execute_with(graph, key, command_id, ExecutionOptions::default())
+    }
+
+    /// Starts executing a graph by triggering a command of a node with options.
+    pub fn execute_with(
+        &mut self,
+        graph: Graph,
+        key: &str,
+        command_id: &str,
+        options: ExecutionOptions,
+    ) -> Result<ExecutionHandle, EngineError> {
+        let plan = Arc::new(ExecutionPlan::compile(&graph, &self.library)?);
+        let command = plan.find_command(key, command_id)?;
+        self.start(plan, vec![command], options)
+    }
+
+    /// Queues every `action` node of a graph to start executing it.
+    ///
+    /// Nothing is handled until the executor is stepped or ran.
+    pub fn execute_actions(&mut self, graph: Graph) -> Result<ExecutionHandle, EngineError> {
+        self.execute_actions_with(graph, ExecutionOptions::default())
+    }
+
+    /// Starts executing a graph by triggering every `action` node once with options.
+    pub fn execute_actions_with(
+        &mut self,
+        graph: Graph,
+        options: ExecutionOptions,
+    ) -> Result<ExecutionHandle, EngineError> {
+        let plan = Arc::new(ExecutionPlan::compile(&graph, &self.library)?);
+        let commands = plan.get_actions();
+        self.start(plan, commands, options)
+    }
+
+    /// Returns queued instructions in order they were dispatched.
+    pub fn pending(&self) -> &VecDeque<Instruction> {
+        &self.queue
+    }
+
+    /// Returns the instruction `step` handles next, if any is queued.
+    ///
+    /// It is the first queued instruction that is due, unless a replayed execution expects
+    /// another one.
+    pub fn next(&self) -> Option<&Instruction> {
+        self.queue.get(self.next_index())
+    }
+
+    /// Returns whether no instructions are queued.
+    pub fn is_idle(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Queues recorded triggers to replay an execution of a graph. Nothing is handled
+    /// until the executor is stepped or ran.
+    ///
+    /// Instructions of a replayed execution are handled in recorded order and
+    /// non-deterministic nodes produce recorded values. Execution fails if it diverges
+    /// from the recording.
+    pub fn replay(
+        &mut self,
+        graph: Graph,
+        recording: Recording,
+    ) -> Result<ExecutionHandle, EngineError> {
+        let plan = Arc::new(ExecutionPlan::compile(&graph, &self.library)?);
+        let commands = recording
+            .triggers
+            .iter()
+            .map(|trigger| plan.find_command(&trigger.node, &trigger.command))
+            .collect::<Result<Vec<_>, _>>()?;
+        let recorder = Arc::new(Recorder::replay(recording));
+        self.start(plan, commands, ExecutionOptions::new().recorder(recorder))
+    }
+
+    /// Handles the next queued instruction. Returns `false` if the queue was empty.
+    ///
+    /// Instructions are handled in FIFO order, except that the next recorded instruction of
+    /// a replayed execution goes first and instructions that are not due yet, such as
+    /// retries backing off, are skipped. If no instruction is due, waits for the earliest
+    /// one, unless its execution is cancelled in the meantime.
+    pub fn step(&mut self) -> bool {
+        let index = self.next_index();
+        let instruction = match self.queue.remove(index) {
+            Some(instruction) => instruction,
+            None => return false,
+        };
+        let execution = &instruction.context.execution;
+        if let Some(not_before) = instruction.not_before.filter(|_| !execution.is_finished()) {
+            let delay = not_before.saturating_duration_since(Instant::now());
+            execution.token().wait_timeout(delay);
+        }
+        let queue = &mut self.queue;
+        self.dispatcher.handle(instruction, |next| {
+            queue.push_back(next);
+            Ok(())
+        });
+        self.dispatcher
+            .metrics()
+            .record(|| Sample::QueueDepth(self.queue.len()));
+        true
+    }
+
+    /// Handles instructions until the queue is empty. Returns the number of handled
+    /// instructions.
+    pub fn run_until_idle(&mut self) -> usize {
+        let mut steps = 0;
+        while self.step() {
+            steps += 1;
+        }
+        steps
+    }
+
+    fn next_index(&self) -> usize {
+        let now = Instant::now();
+        let replayed = self.queue.iter().position(|instruction| {
+            instruction
+                .context
+                .execution
+                .recorder()
+                .is_some_and(|recorder| recorder.is_replaying() && recorder.is_next(instruction))
+        });
+        let due = || {
+            self.queue.iter().position(|instruction| {
+                instruction.is_due(now) || instruction.context.execution.is_finished()
+            })
+        };
+        let earliest = || (0..self.queue.len()).min_by_key(|index| self.queue[*index].not_before);
+        replayed.or_else(due).or_else(earliest).unwrap_or(0)
+    }
+
+    fn start(
+        &mut self,
+        plan: Arc<ExecutionPlan>,
+        entries: Vec<CommandIndex>,
+        options: ExecutionOptions,
+    ) -> Result<ExecutionHandle, EngineError> {
+        plan.check_entries(&entries)?;
+        let execution = Arc::new(Execution::with_options(
+            self.next_execution_id,
+            plan,
+            options,
+        ));
+        self.next_execution_id += 1;
+
+        let queue = &mut self.queue;
+        execution.start(
+            &entries,
+            self.dispatcher.tracer(),
+            self.dispatcher.metrics(),
+            |instruction| queue.push_back(instruction),
+        );
+
+        Ok(ExecutionHandle::new(execution, entries))
+    }
+}
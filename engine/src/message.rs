@@ -1,46 +1,104 @@
 //! Types for inter-worker messaging.
 
 use std::sync::Arc;
+use std::time::Instant;
 
-use graph::graph::placed_node::PlacedNode;
-use graph::graph::Graph;
+use crate::execution::Execution;
+use crate::library::Values;
+use crate::plan::{CommandIndex, ExecutionPlan, NodeIndex};
 
 /// Context for current point of execution.
 #[derive(Debug)]
 pub struct Context {
-    pub graph: Arc<Graph>,
-    pub node: PlacedNode,
+    /// Execution this context belongs to.
+    pub execution: Arc<Execution>,
+    /// Plan being executed.
+    pub plan: Arc<ExecutionPlan>,
+    /// Node being executed.
+    pub node: NodeIndex,
 }
 
 /// Represents a message that triggers a command.
 #[derive(Debug)]
 pub struct Instruction {
+    /// Context of execution.
     pub context: Context,
-    pub command_id: String,
+    /// Command to execute.
+    pub command: CommandIndex,
+    /// Payload of an event that triggered the command.
+    pub payload: Values,
+    /// Edge that was followed to the command, unless it is an entry point.
+    pub edge: Option<String>,
+    /// When the instruction was created.
+    pub queued_at: Instant,
+    /// Number of edges followed from an entry instruction to this one.
+    pub depth: usize,
+    /// Position among pending instructions of a checkpointed execution.
+    pub sequence: Option<u64>,
+    /// Attempt at the command, counted from 1.
+    pub attempt: u32,
+    /// Time before which the instruction is not handled, e.g. to back off a retry.
+    pub not_before: Option<Instant>,
 }
 
 /// Message represents a type for communication between workers.
 #[derive(Debug)]
 pub enum Message {
+    /// Instructs a worker to execute a command.
     Instruction(Instruction),
+    /// Instructs a worker to stop.
+    Stop,
 }
 
 impl Context {
     /// Constructs a `Context`.
-    pub fn new(graph: Arc<Graph>, node: &PlacedNode) -> Self {
+    pub fn new(execution: Arc<Execution>, node: NodeIndex) -> Self {
         Context {
-            graph,
-            node: node.clone(),
+            plan: Arc::clone(execution.plan()),
+            execution,
+            node,
         }
     }
 }
 
+impl Instruction {
+    /// Constructs an `Instruction` for a command of an execution's plan.
+    pub fn new(execution: Arc<Execution>, command: CommandIndex, payload: Values) -> Self {
+        let node = execution.plan().commands[command].node;
+        Instruction {
+            context: Context::new(execution, node),
+            command,
+            payload,
+            edge: None,
+            queued_at: Instant::now(),
+            depth: 0,
+            sequence: None,
+            attempt: 1,
+            not_before: None,
+        }
+    }
+
+    /// Returns an `Instruction` marked as reached through an edge.
+    pub fn via(mut self, edge: &str) -> Self {
+        self.edge = Some(String::from(edge));
+        self
+    }
+
+    /// Returns an `Instruction` placed at a depth of an event chain.
+    pub fn at_depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Returns whether the instruction can be handled at a time.
+    pub fn is_due(&self, now: Instant) -> bool {
+        self.not_before.is_none_or(|not_before| not_before <= now)
+    }
+}
+
 impl Message {
-    /// Constructs `Message::Instruction`.
-    pub fn instruction(context: Context, command_id: &str) -> Self {
-        Message::Instruction(Instruction {
-            context,
-            command_id: String::from(command_id),
-        })
+    /// Constructs `Message::Instruction` for a command of an execution's plan.
+    pub fn instruction(execution: Arc<Execution>, command: CommandIndex, payload: Values) -> Self {
+        Message::Instruction(Instruction::new(execution, command, payload))
     }
 }
@@ -0,0 +1,279 @@
+//! Execution metrics and their Prometheus text exposition.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug, Formatter, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use crate::message::Instruction;
+
+/// Upper bounds of histogram buckets, in seconds.
+pub const BUCKETS: [f64; 8] = [0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 1.0];
+
+/// Identifies a command of a placed node.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CommandLabels {
+    /// Id of the schema node.
+    pub node: String,
+    /// Key of the placed node.
+    pub key: String,
+    /// Id of the command.
+    pub command: String,
+}
+
+/// Measurement taken by an engine.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sample {
+    /// An instruction was queued for a command.
+    Dispatched(CommandLabels),
+    /// An instruction was handled.
+    Handled {
+        /// Handled command.
+        labels: CommandLabels,
+        /// Time between queueing and handling.
+        queue_wait: Duration,
+        /// Time spent handling.
+        duration: Duration,
+        /// Whether handling failed.
+        failed: bool,
+    },
+    /// Number of messages waiting in a queue.
+    QueueDepth(usize),
+    /// Share of time a worker spent handling instructions since it started.
+    WorkerUtilization {
+        /// Worker's id.
+        worker: u64,
+        /// Ratio between 0 and 1.
+        ratio: f64,
+    },
+}
+
+/// Receives samples taken by an engine.
+pub trait MetricsSink: Send + Sync {
+    /// Stores a sample.
+    fn record(&self, sample: Sample);
+}
+
+/// Takes samples into a sink, if there is one.
+#[derive(Clone, Default)]
+pub struct Metrics {
+    sink: Option<Arc<dyn MetricsSink>>,
+}
+
+/// Aggregates samples and renders them in Prometheus text format.
+#[derive(Debug, Default)]
+pub struct PrometheusRegistry {
+    state: Mutex<Registry>,
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    commands: BTreeMap<CommandLabels, CommandMetrics>,
+    queue_depth: usize,
+    workers: BTreeMap<u64, f64>,
+}
+
+#[derive(Debug, Default)]
+struct CommandMetrics {
+    dispatched: u64,
+    errors: u64,
+    duration: Histogram,
+    queue_wait: Histogram,
+}
+
+#[derive(Debug, Default)]
+struct Histogram {
+    buckets: [u64; BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl CommandLabels {
+    /// Constructs `CommandLabels` of an instruction's command.
+    pub fn new(instruction: &Instruction) -> Self {
+        let plan = &instruction.context.plan;
+        let command = &plan.commands[instruction.command];
+        let node = &plan.nodes[command.node];
+        CommandLabels {
+            node: node.node.id.clone(),
+            key: node.key.clone(),
+            command: command.reference.property.id.clone(),
+        }
+    }
+}
+
+impl Debug for Metrics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Metrics")
+            .field("enabled", &self.is_enabled())
+            .finish()
+    }
+}
+
+impl Metrics {
+    /// Constructs `Metrics` that records to a sink.
+    pub fn new(sink: Arc<dyn MetricsSink>) -> Self {
+        Metrics { sink: Some(sink) }
+    }
+
+    /// Returns whether samples are collected.
+    pub fn is_enabled(&self) -> bool {
+        self.sink.is_some()
+    }
+
+    /// Records a sample. Sample is only constructed if metrics are enabled.
+    pub fn record<F>(&self, sample: F)
+    where
+        F: FnOnce() -> Sample,
+    {
+        if let Some(sink) = &self.sink {
+            sink.record(sample());
+        }
+    }
+}
+
+impl PrometheusRegistry {
+    /// Constructs an empty `PrometheusRegistry`.
+    pub fn new() -> Self {
+        PrometheusRegistry::default()
+    }
+
+    /// Renders aggregated metrics in Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let registry = self.lock();
+        let mut out = String::new();
+
+        out.push_str("# HELP engine_dispatched_total Instructions dispatched to a command.\n");
+        out.push_str("# TYPE engine_dispatched_total counter\n");
+        for (labels, metrics) in registry.commands.iter() {
+            let _ = writeln!(
+                out,
+                "engine_dispatched_total{{{}}} {}",
+                format_labels(labels),
+                metrics.dispatched
+            );
+        }
+
+        out.push_str("# HELP engine_errors_total Instructions of a command that failed.\n");
+        out.push_str("# TYPE engine_errors_total counter\n");
+        for (labels, metrics) in registry.commands.iter() {
+            let _ = writeln!(
+                out,
+                "engine_errors_total{{{}}} {}",
+                format_labels(labels),
+                metrics.errors
+            );
+        }
+
+        out.push_str("# HELP engine_handler_duration_seconds Time spent handling a command.\n");
+        out.push_str("# TYPE engine_handler_duration_seconds histogram\n");
+        for (labels, metrics) in registry.commands.iter() {
+            metrics
+                .duration
+                .render(&mut out, "engine_handler_duration_seconds", labels);
+        }
+
+        out.push_str("# HELP engine_queue_wait_seconds Time instructions waited in a queue.\n");
+        out.push_str("# TYPE engine_queue_wait_seconds histogram\n");
+        for (labels, metrics) in registry.commands.iter() {
+            metrics
+                .queue_wait
+                .render(&mut out, "engine_queue_wait_seconds", labels);
+        }
+
+        out.push_str("# HELP engine_queue_depth Messages waiting in the queue.\n");
+        out.push_str("# TYPE engine_queue_depth gauge\n");
+        let _ = writeln!(out, "engine_queue_depth {}", registry.queue_depth);
+
+        out.push_str("# HELP engine_worker_utilization Share of time a worker was busy.\n");
+        out.push_str("# TYPE engine_worker_utilization gauge\n");
+        for (worker, ratio) in registry.workers.iter() {
+            let _ = writeln!(
+                out,
+                "engine_worker_utilization{{worker=\"{}\"}} {}",
+                worker, ratio
+            );
+        }
+
+        out
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Registry> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl MetricsSink for PrometheusRegistry {
+    fn record(&self, sample: Sample) {
+        let mut registry = self.lock();
+        match sample {
+            Sample::Dispatched(labels) => {
+                registry.commands.entry(labels).or_default().dispatched += 1;
+            }
+            Sample::Handled {
+                labels,
+                queue_wait,
+                duration,
+                failed,
+            } => {
+                let metrics = registry.commands.entry(labels).or_default();
+                metrics.queue_wait.observe(queue_wait);
+                metrics.duration.observe(duration);
+                if failed {
+                    metrics.errors += 1;
+                }
+            }
+            Sample::QueueDepth(depth) => registry.queue_depth = depth,
+            Sample::WorkerUtilization { worker, ratio } => {
+                registry.workers.insert(worker, ratio);
+            }
+        }
+    }
+}
+
+impl Histogram {
+    fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        if let Some(bucket) = BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.buckets[bucket] += 1;
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &CommandLabels) {
+        let labels = format_labels(labels);
+        let mut cumulative = 0;
+        for (bound, count) in BUCKETS.iter().zip(self.buckets.iter()) {
+            cumulative += count;
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{},le=\"{}\"}} {}",
+                name, labels, bound, cumulative
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{},le=\"+Inf\"}} {}",
+            name, labels, self.count
+        );
+        let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, self.sum);
+        let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, self.count);
+    }
+}
+
+fn format_labels(labels: &CommandLabels) -> String {
+    format!(
+        "node=\"{}\",key=\"{}\",command=\"{}\"",
+        escape(&labels.node),
+        escape(&labels.key),
+        escape(&labels.command)
+    )
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
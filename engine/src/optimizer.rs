@@ -0,0 +1,142 @@
+//! Optimization passes over graphs.
+
+use std::collections::HashMap;
+
+use graph::graph::edge::Hook;
+use graph::graph::Graph;
+use graph::value::Value;
+
+use crate::error::EngineError;
+use crate::library::{Library, Values};
+
+/// Pure node that was evaluated ahead of time and removed from a graph.
+#[derive(Debug, Clone)]
+pub struct FoldedNode {
+    /// Key of a removed node.
+    pub key: String,
+    /// Computed outputs by ids.
+    pub outputs: Values,
+}
+
+/// Input that was assigned a folded value in place of an edge.
+#[derive(Debug, Clone)]
+pub struct FoldedInput {
+    /// Key of a node owning the input.
+    pub key: String,
+    /// Id of the input.
+    pub property_id: String,
+    /// Assigned value.
+    pub value: Value,
+}
+
+/// Describes changes made by `fold_constants`.
+#[derive(Debug, Clone, Default)]
+pub struct FoldReport {
+    /// Folded nodes, in order of evaluation.
+    pub nodes: Vec<FoldedNode>,
+    /// Inputs that now hold folded values.
+    pub inputs: Vec<FoldedInput>,
+}
+
+/// Evaluates pure nodes whose inputs are all constant and rewrites the graph so
+/// that downstream inputs hold computed values directly.
+///
+/// Non-deterministic nodes and nodes with inputs bound to graph's parameters are never
+/// folded, and nodes bound to graph's results are evaluated but kept in the graph.
+pub fn fold_constants(graph: &mut Graph, library: &Library) -> Result<FoldReport, EngineError> {
+    let mut keys: Vec<String> = graph
+        .nodes
+        .values()
+        .filter(|placed_node| library.is_pure(&placed_node.node))
+        .filter(|placed_node| library.is_deterministic(&placed_node.node.id))
+        .filter(|placed_node| {
+            graph
+                .parameters
+                .values()
+                .all(|port| port.key != placed_node.key)
+        })
+        .map(|placed_node| placed_node.key.clone())
+        .collect();
+    keys.sort();
+
+    let mut folded: HashMap<String, Values> = HashMap::new();
+    let mut report = FoldReport::default();
+    loop {
+        let mut progress = false;
+        for key in keys.iter() {
+            if folded.contains_key(key) {
+                continue;
+            }
+            let inputs = match constant_inputs(graph, key, &folded) {
+                Some(inputs) => inputs,
+                None => continue,
+            };
+            let placed_node = graph.get_node(key);
+            let function = library.get_function(&placed_node.node.id).unwrap();
+            let outputs = function(&inputs).map_err(|e| {
+                EngineError::from(format!("Failed to fold '{}': {}", key, e.message))
+            })?;
+
+            if graph.results.values().all(|port| port.key != *key) {
+                report.nodes.push(FoldedNode {
+                    key: key.clone(),
+                    outputs: outputs.clone(),
+                });
+            }
+            folded.insert(key.clone(), outputs);
+            progress = true;
+        }
+        if !progress {
+            break;
+        }
+    }
+
+    for node in report.nodes.iter() {
+        let placed_node = graph.get_node(&node.key).clone();
+        for (output_id, value) in node.outputs.iter() {
+            let source = Hook::new(
+                placed_node.clone(),
+                placed_node.get_property(output_id).clone(),
+            );
+            for target in graph.edge_map.get_outputs(&source) {
+                if report.nodes.iter().any(|node| node.key == target.node.key) {
+                    continue;
+                }
+                graph.assign(&target.node.key, target.property.id(), value.clone())?;
+                report.inputs.push(FoldedInput {
+                    key: target.node.key.clone(),
+                    property_id: target.property.id().clone(),
+                    value: value.clone(),
+                });
+            }
+        }
+    }
+    for node in report.nodes.iter() {
+        graph.remove_node(&node.key);
+    }
+
+    Ok(report)
+}
+
+/// Returns inputs of a node if all of them are assigned or come from folded nodes.
+fn constant_inputs(graph: &Graph, key: &str, folded: &HashMap<String, Values>) -> Option<Values> {
+    let placed_node = graph.get_node(key);
+    let mut inputs = Values::new();
+    for property in placed_node
+        .node
+        .properties
+        .values()
+        .filter(|property| property.is_input())
+    {
+        let target = Hook::new(placed_node.clone(), property.clone());
+        let value = match graph.edge_map.get_input(&target) {
+            Some(source) => folded
+                .get(&source.node.key)?
+                .get(source.property.id())?
+                .clone(),
+            None => placed_node.values.get(property.id())?.value.clone(),
+        };
+        inputs.insert(property.id().clone(), value);
+    }
+    Some(inputs)
+}
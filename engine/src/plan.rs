@@ -0,0 +1,425 @@
+//! Compiled form of a graph that workers execute.
+//!
+//! Compilation resolves every node against a `Library`, numbers all values of
+//! inputs and outputs as slots and precomputes connections between them, so that
+//! executing an instruction only indexes into vectors.
+
+use std::collections::HashMap;
+
+use graph::graph::edge::Edge;
+use graph::graph::guard::Guard;
+use graph::graph::Graph;
+use graph::schema::node::Node;
+use graph::value::{DataType, Value};
+
+use crate::error::EngineError;
+use crate::library::basic::action;
+use crate::library::{CommandReference, EventReference, Function, Library, Values};
+use crate::retry::RetryPolicy;
+
+/// Index of a node in `ExecutionPlan::nodes`.
+pub type NodeIndex = usize;
+/// Index of a slot in `ExecutionPlan::slots`.
+pub type SlotIndex = usize;
+/// Index of a command in `ExecutionPlan::commands`.
+pub type CommandIndex = usize;
+/// Index of an event in `ExecutionPlan::events`.
+pub type EventIndex = usize;
+
+/// Describes where a slot gets its value from.
+#[derive(Debug, Clone)]
+pub enum SlotSource {
+    /// Value assigned to an unconnected input.
+    Constant(Value),
+    /// Value of an output slot connected by an edge.
+    Link(SlotIndex),
+    /// Value computed by slot's node.
+    Output,
+}
+
+/// Holds a value of a node's input or output.
+#[derive(Debug, Clone)]
+pub struct Slot {
+    /// Node owning this slot.
+    pub node: NodeIndex,
+    /// Id of an input or output property.
+    pub property_id: String,
+    /// Type of slot's value.
+    pub data_type: DataType,
+    /// Source of slot's value.
+    pub source: SlotSource,
+}
+
+/// Compiled placed node.
+#[derive(Debug, Clone)]
+pub struct PlanNode {
+    /// Key of a placed node.
+    pub key: String,
+    /// Schema of a node.
+    pub node: Node,
+    /// Function computing outputs if node is pure.
+    pub function: Option<Function>,
+    /// Whether outputs only depend on inputs.
+    pub deterministic: bool,
+    /// Policy for retrying failed commands, if the placed node or its schema has one.
+    pub retry: Option<RetryPolicy>,
+    /// Input slots, ordered by property id.
+    pub inputs: Vec<SlotIndex>,
+    /// Output slots, ordered by property id.
+    pub outputs: Vec<SlotIndex>,
+}
+
+/// Compiled command of a placed node.
+#[derive(Debug, Clone)]
+pub struct PlanCommand {
+    /// Node owning this command.
+    pub node: NodeIndex,
+    /// Resolved command.
+    pub reference: CommandReference,
+    /// Slots that must be resolved to execute this command.
+    pub dependencies: Vec<SlotIndex>,
+}
+
+/// Describes where a guard reads its operand from.
+#[derive(Debug, Clone)]
+pub enum GuardOperand {
+    /// Field of event's payload.
+    Payload(String),
+    /// Output slot of event's node.
+    Output(SlotIndex),
+}
+
+/// Compiled guard of an edge.
+#[derive(Debug, Clone)]
+pub struct PlanGuard {
+    /// Guard declared on an edge.
+    pub guard: Guard,
+    /// Resolved operand.
+    pub operand: GuardOperand,
+}
+
+/// Command triggered by an event.
+#[derive(Debug, Clone)]
+pub struct PlanTarget {
+    /// Triggered command.
+    pub command: CommandIndex,
+    /// Key of an edge connecting event to command.
+    pub edge: String,
+    /// Condition for triggering the command.
+    pub guard: Option<PlanGuard>,
+}
+
+/// Compiled event of a placed node.
+#[derive(Debug, Clone)]
+pub struct PlanEvent {
+    /// Node owning this event.
+    pub node: NodeIndex,
+    /// Resolved event.
+    pub reference: EventReference,
+    /// Commands triggered by this event.
+    pub targets: Vec<PlanTarget>,
+}
+
+/// Graph lowered into a form suitable for execution.
+#[derive(Debug, Clone)]
+pub struct ExecutionPlan {
+    /// All nodes, ordered by key.
+    pub nodes: Vec<PlanNode>,
+    /// All value slots.
+    pub slots: Vec<Slot>,
+    /// All commands.
+    pub commands: Vec<PlanCommand>,
+    /// All events.
+    pub events: Vec<PlanEvent>,
+    /// Slots bound to graph's results by result ids.
+    pub results: HashMap<String, SlotIndex>,
+    node_indices: HashMap<String, NodeIndex>,
+    slot_indices: HashMap<(NodeIndex, String), SlotIndex>,
+    command_indices: HashMap<(NodeIndex, String), CommandIndex>,
+    event_indices: HashMap<(NodeIndex, String), EventIndex>,
+}
+
+impl PlanCommand {
+    /// Checks that a payload provides all parameters of this command.
+    pub fn check_payload(&self, payload: &Values) -> Result<(), EngineError> {
+        for parameter in self.reference.property.parameters.iter() {
+            match payload.get(&parameter.id) {
+                Some(value) if value.data_type() == parameter.data_type => {}
+                Some(value) => {
+                    return Err(EngineError::from(format!(
+                        "Parameter '{}' of '{}' expects {:?}, got {:?}.",
+                        parameter.id, self.reference.property.id, parameter.data_type, value
+                    )));
+                }
+                None => {
+                    return Err(EngineError::from(format!(
+                        "Missing parameter '{}' of '{}'.",
+                        parameter.id, self.reference.property.id
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PlanTarget {
+    /// Returns whether an event with given payload triggers this target.
+    ///
+    /// Outputs of event's node are resolved with `resolve` only if guard reads them.
+    pub fn allows<F>(&self, payload: &Values, resolve: F) -> Result<bool, EngineError>
+    where
+        F: FnOnce(SlotIndex) -> Result<Value, EngineError>,
+    {
+        let guard = match &self.guard {
+            Some(guard) => guard,
+            None => return Ok(true),
+        };
+        let operand = match &guard.operand {
+            GuardOperand::Payload(id) => payload.get(id).cloned().ok_or_else(|| {
+                EngineError::from(format!(
+                    "Guard '{}' of '{}' reads missing payload field.",
+                    guard.guard, self.edge
+                ))
+            })?,
+            GuardOperand::Output(slot) => resolve(*slot)?,
+        };
+        Ok(guard.guard.evaluate(&operand)?)
+    }
+}
+
+impl ExecutionPlan {
+    /// Compiles a graph into an `ExecutionPlan`.
+    pub fn compile(graph: &Graph, library: &Library) -> Result<Self, EngineError> {
+        let mut plan = ExecutionPlan {
+            nodes: Vec::with_capacity(graph.nodes.len()),
+            slots: Vec::new(),
+            commands: Vec::new(),
+            events: Vec::new(),
+            results: HashMap::new(),
+            node_indices: HashMap::new(),
+            slot_indices: HashMap::new(),
+            command_indices: HashMap::new(),
+            event_indices: HashMap::new(),
+        };
+
+        let mut keys: Vec<&String> = graph.nodes.keys().collect();
+        keys.sort();
+        for key in keys {
+            plan.add_node(graph, library, key)?;
+        }
+        for edge in graph.edge_map.edges.values() {
+            plan.add_edge(edge)?;
+        }
+        for event in plan.events.iter_mut() {
+            event.targets.sort_by(|a, b| a.edge.cmp(&b.edge));
+        }
+        for port in graph.results.values() {
+            let slot = plan
+                .get_node_index(&port.key)
+                .and_then(|node| plan.get_slot_index(node, &port.property_id))
+                .ok_or_else(|| {
+                    EngineError::from(format!("Result '{}' is not bound to a node.", port.id))
+                })?;
+            plan.results.insert(port.id.clone(), slot);
+        }
+
+        Ok(plan)
+    }
+
+    /// Returns index of a node by key.
+    pub fn get_node_index(&self, key: &str) -> Option<NodeIndex> {
+        self.node_indices.get(key).copied()
+    }
+
+    /// Returns index of a node's input or output slot.
+    pub fn get_slot_index(&self, node: NodeIndex, property_id: &str) -> Option<SlotIndex> {
+        self.slot_indices
+            .get(&(node, String::from(property_id)))
+            .copied()
+    }
+
+    /// Returns index of a node's command.
+    pub fn get_command_index(&self, node: NodeIndex, command_id: &str) -> Option<CommandIndex> {
+        self.command_indices
+            .get(&(node, String::from(command_id)))
+            .copied()
+    }
+
+    /// Returns index of a node's event.
+    pub fn get_event_index(&self, node: NodeIndex, event_id: &str) -> Option<EventIndex> {
+        self.event_indices
+            .get(&(node, String::from(event_id)))
+            .copied()
+    }
+
+    /// Returns index of a command by node key and command id.
+    pub fn find_command(&self, key: &str, command_id: &str) -> Result<CommandIndex, EngineError> {
+        self.get_node_index(key)
+            .and_then(|node| self.get_command_index(node, command_id))
+            .ok_or_else(|| {
+                EngineError::from(format!("Command '{}#{}' not found.", key, command_id))
+            })
+    }
+
+    /// Fails if an entry command can't be triggered without a payload.
+    pub fn check_entries(&self, entries: &[CommandIndex]) -> Result<(), EngineError> {
+        entries
+            .iter()
+            .try_for_each(|command| self.commands[*command].check_payload(&Values::new()))
+    }
+
+    /// Returns indices of `trigger` commands of all `action` nodes.
+    pub fn get_actions(&self) -> Vec<CommandIndex> {
+        (0..self.commands.len())
+            .filter(|command| {
+                let command = &self.commands[*command];
+                command.reference.node.id == action::ID
+                    && command.reference.property.id == action::COMMAND_TRIGGER
+            })
+            .collect()
+    }
+
+    fn add_node(&mut self, graph: &Graph, library: &Library, key: &str) -> Result<(), EngineError> {
+        let placed_node = graph.get_node(key);
+        if !library.schema.nodes.contains_key(&placed_node.node.id) {
+            return Err(EngineError::from(format!(
+                "Node '{}' of '{}' is not supported.",
+                placed_node.node.id, key
+            )));
+        }
+
+        let index = self.nodes.len();
+        let mut property_ids: Vec<&String> = placed_node.node.properties.keys().collect();
+        property_ids.sort();
+
+        let mut inputs = Vec::new();
+        let mut outputs = Vec::new();
+        let mut commands = Vec::new();
+        for property_id in property_ids {
+            let property = placed_node.get_property(property_id);
+            if property.is_command() {
+                commands.push(property_id);
+                continue;
+            }
+            if property.is_event() {
+                self.event_indices
+                    .insert((index, property_id.clone()), self.events.len());
+                self.events.push(PlanEvent {
+                    node: index,
+                    reference: library.get_event(&placed_node.node.id, property_id)?,
+                    targets: Vec::new(),
+                });
+                continue;
+            }
+
+            let source = if property.is_input() {
+                let value = placed_node.values.get(property_id).ok_or_else(|| {
+                    EngineError::from(format!("No value assigned for '{}#{}'", key, property_id))
+                })?;
+                SlotSource::Constant(value.value.clone())
+            } else {
+                SlotSource::Output
+            };
+            let slot = self.slots.len();
+            self.slot_indices.insert((index, property_id.clone()), slot);
+            self.slots.push(Slot {
+                node: index,
+                property_id: property_id.clone(),
+                data_type: *property.data_type().unwrap(),
+                source,
+            });
+            if property.is_input() {
+                inputs.push(slot);
+            } else {
+                outputs.push(slot);
+            }
+        }
+
+        for command_id in commands {
+            self.command_indices
+                .insert((index, command_id.clone()), self.commands.len());
+            self.commands.push(PlanCommand {
+                node: index,
+                reference: library.get_command(&placed_node.node.id, command_id)?,
+                dependencies: inputs.clone(),
+            });
+        }
+
+        self.node_indices.insert(String::from(key), index);
+        self.nodes.push(PlanNode {
+            key: String::from(key),
+            node: placed_node.node.clone(),
+            function: library.get_function(&placed_node.node.id),
+            deterministic: library.is_deterministic(&placed_node.node.id),
+            retry: placed_node
+                .retry
+                .as_ref()
+                .or_else(|| library.get_retry_policy(&placed_node.node.id))
+                .cloned(),
+            inputs,
+            outputs,
+        });
+
+        Ok(())
+    }
+
+    fn add_edge(&mut self, edge: &Edge) -> Result<(), EngineError> {
+        let (source, target) = (&edge.source, &edge.target);
+        let missing = || EngineError::from(format!("Edge '{}' is not connected to a node.", edge));
+        let source_node = self.get_node_index(&source.node.key).ok_or_else(missing)?;
+        let target_node = self.get_node_index(&target.node.key).ok_or_else(missing)?;
+
+        if source.property.is_event() {
+            let event = self
+                .get_event_index(source_node, source.property.id())
+                .ok_or_else(missing)?;
+            let command = self
+                .get_command_index(target_node, target.property.id())
+                .ok_or_else(missing)?;
+            let guard = match &edge.guard {
+                Some(guard) => {
+                    let operand = if self.events[event]
+                        .reference
+                        .property
+                        .get_field(&guard.operand)
+                        .is_some()
+                    {
+                        GuardOperand::Payload(guard.operand.clone())
+                    } else {
+                        GuardOperand::Output(
+                            self.get_slot_index(source_node, &guard.operand)
+                                .ok_or_else(missing)?,
+                        )
+                    };
+                    Some(PlanGuard {
+                        guard: guard.clone(),
+                        operand,
+                    })
+                }
+                None => None,
+            };
+            self.events[event].targets.push(PlanTarget {
+                command,
+                edge: edge.to_string(),
+                guard,
+            });
+        } else {
+            let output = self
+                .get_slot_index(source_node, source.property.id())
+                .ok_or_else(missing)?;
+            let input = self
+                .get_slot_index(target_node, target.property.id())
+                .ok_or_else(missing)?;
+            if let SlotSource::Link(_) = self.slots[input].source {
+                return Err(EngineError::from(format!(
+                    "Input '{}' is connected more than once.",
+                    target
+                )));
+            }
+            self.slots[input].source = SlotSource::Link(output);
+        }
+
+        Ok(())
+    }
+}
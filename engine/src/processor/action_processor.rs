@@ -1,29 +1,35 @@
+//! Processor for `action` nodes.
+
 use std::sync::Arc;
 
+use crate::error::EngineError;
 use crate::library;
-use crate::library::Library;
+use crate::library::{Library, Values};
 use crate::processor::{Processor, Router};
 
+/// Handles commands of `action` nodes.
 pub struct ActionProcessor {
     router: Router,
 }
 
 impl ActionProcessor {
-    pub fn new(lib: &Arc<Library>) -> Self {
+    /// Constructs an `ActionProcessor`.
+    pub fn new(lib: &Arc<Library>) -> Result<Self, EngineError> {
         let trigger_command = lib.get_command(
             library::basic::action::ID,
             library::basic::action::COMMAND_TRIGGER,
-        );
+        )?;
         let triggered_event = lib.get_event(
             library::basic::action::ID,
             library::basic::action::EVENT_TRIGGERED,
-        );
+        )?;
 
-        let router = Router::new();
-        ActionProcessor { router }
+        let mut router = Router::new();
+        router.route(&trigger_command, move |invocation| {
+            invocation.emit(&triggered_event, Values::new())
+        });
+        Ok(ActionProcessor { router })
     }
-
-    fn handle_trigger(&self) {}
 }
 
 impl Processor for ActionProcessor {
@@ -1,13 +1,291 @@
+//! Processors implement commands of nodes.
+
+use std::sync::Arc;
+
+use graph::value::Value;
+
+use crate::error::EngineError;
+use crate::execution::CancellationToken;
+use crate::library::{CommandKey, CommandReference, EventReference, InputReference, Values};
+use crate::message::Instruction;
+use crate::plan::{EventIndex, PlanNode, SlotIndex};
+use crate::record::{Recorder, Step};
+use crate::resolver::Resolver;
+use crate::trace::{TraceEvent, Tracer};
+
 pub mod action_processor;
+pub mod printer_processor;
+pub mod repeat_processor;
 
-pub struct Router {}
+/// Handles a command of a node.
+pub type Handler = Box<dyn Fn(&mut Invocation<'_>) -> Result<(), EngineError> + Send + Sync>;
 
-pub trait Processor {
+/// Routes instructions to handlers by keys of their commands.
+#[derive(Default)]
+pub struct Router {
+    handlers: Vec<Option<Handler>>,
+}
+
+/// Processor handles commands of one or more nodes.
+pub trait Processor: Send + Sync {
+    /// Returns processor's router.
     fn router(&self) -> &Router;
 }
 
+/// Gives a handler access to the instruction being executed.
+pub struct Invocation<'a> {
+    instruction: &'a Instruction,
+    resolver: Resolver<'a>,
+    events: Vec<(EventIndex, Values)>,
+    targets: usize,
+    continuation: Option<Values>,
+    tracer: Tracer,
+}
+
 impl Router {
+    /// Constructs a `Router`.
     pub fn new() -> Self {
-        Router {}
+        Router {
+            handlers: Vec::new(),
+        }
+    }
+
+    /// Registers a handler for a command.
+    /// # Panics
+    /// If command already has a handler.
+    pub fn route<F>(&mut self, command: &CommandReference, handler: F)
+    where
+        F: Fn(&mut Invocation<'_>) -> Result<(), EngineError> + Send + Sync + 'static,
+    {
+        if route(&mut self.handlers, command, Box::new(handler)).is_some() {
+            panic!(
+                "duplicate handler for '{}#{}'",
+                command.node.id, command.property.id
+            );
+        }
+    }
+
+    /// Returns a handler for an instruction's command.
+    pub fn resolve(&self, instruction: &Instruction) -> Option<&Handler> {
+        let command = &instruction.context.plan.commands[instruction.command];
+        self.get(command.reference.key)
+    }
+
+    /// Returns a handler for a command by its key.
+    pub fn get(&self, key: CommandKey) -> Option<&Handler> {
+        self.handlers.get(key)?.as_ref()
+    }
+
+    /// Returns keys of commands with a handler.
+    pub(crate) fn keys(&self) -> impl Iterator<Item = CommandKey> + '_ {
+        self.handlers
+            .iter()
+            .enumerate()
+            .filter(|(_, handler)| handler.is_some())
+            .map(|(key, _)| key)
+    }
+}
+
+impl<'a> Invocation<'a> {
+    /// Constructs an `Invocation` of an instruction.
+    pub fn new(instruction: &'a Instruction) -> Self {
+        Invocation::with_tracer(instruction, &Tracer::default())
+    }
+
+    /// Constructs an `Invocation` of an instruction that traces resolved inputs.
+    pub fn with_tracer(instruction: &'a Instruction, tracer: &Tracer) -> Self {
+        Invocation {
+            instruction,
+            resolver: Resolver::new(&instruction.context.plan),
+            events: Vec::new(),
+            targets: 0,
+            continuation: None,
+            tracer: tracer.clone(),
+        }
+    }
+
+    /// Returns the instruction being executed.
+    pub fn instruction(&self) -> &Instruction {
+        self.instruction
+    }
+
+    /// Returns the node being executed.
+    pub fn node(&self) -> &PlanNode {
+        &self.instruction.context.plan.nodes[self.instruction.context.node]
+    }
+
+    /// Returns cancellation token of the execution. Long running handlers should stop
+    /// once it is cancelled.
+    pub fn token(&self) -> &CancellationToken {
+        self.instruction.context.execution.token()
+    }
+
+    /// Returns whether the execution was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.token().is_cancelled()
+    }
+
+    /// Returns payload of an event that triggered the command.
+    pub fn payload(&self) -> &Values {
+        &self.instruction.payload
+    }
+
+    /// Fails if the execution exceeded its limits, counting commands targeted by fired
+    /// events as dispatched. Long running handlers should check it periodically.
+    pub fn check_limits(&self) -> Result<(), EngineError> {
+        check_limits(self.instruction, self.targets)
+    }
+
+    /// Returns state the executing node stored in the execution.
+    pub fn state(&self) -> Values {
+        let context = &self.instruction.context;
+        context
+            .execution
+            .node_state(&context.plan.nodes[context.node].key)
+    }
+
+    /// Updates state of the executing node in the execution. State is kept separately for
+    /// every execution.
+    pub fn update_state<F, T>(&self, update: F) -> T
+    where
+        F: FnOnce(&mut Values) -> T,
+    {
+        let context = &self.instruction.context;
+        context
+            .execution
+            .update_node_state(&context.plan.nodes[context.node].key, update)
+    }
+
+    /// Returns a value of the executing node's input, computing it from connected nodes if needed.
+    ///
+    /// Outputs of upstream nodes are computed at most once per invocation.
+    pub fn input(&mut self, input: &InputReference) -> Result<Value, EngineError> {
+        let slot = input_slot(self.instruction, input)?;
+        let value = self.resolver.resolve(slot)?;
+        trace_input(&self.tracer, self.instruction, input, &value);
+        Ok(value)
+    }
+
+    /// Records outputs of non-deterministic nodes under a step of execution's recorder.
+    pub fn set_tape(&mut self, recorder: Arc<Recorder>, step: Step) {
+        self.resolver.set_tape(recorder, step);
+    }
+
+    /// Returns a value of any slot in the plan, sharing cache with `input`.
+    pub fn resolve(&mut self, slot: SlotIndex) -> Result<Value, EngineError> {
+        self.resolver.resolve(slot)
+    }
+
+    /// Fires an event of the executing node.
+    pub fn emit(&mut self, event: &EventReference, payload: Values) -> Result<(), EngineError> {
+        let index = event_index(self.instruction, event, &payload)?;
+        self.targets += self.instruction.context.plan.events[index].targets.len();
+        self.events.push((index, payload));
+        Ok(())
+    }
+
+    /// Returns fired events with their payloads, in order of firing.
+    pub fn events(&self) -> &[(EventIndex, Values)] {
+        &self.events
+    }
+
+    /// Takes fired events out of an invocation.
+    pub fn take_events(&mut self) -> Vec<(EventIndex, Values)> {
+        self.targets = 0;
+        std::mem::take(&mut self.events)
+    }
+
+    /// Handles the command again with a payload once fired events are dispatched, so that
+    /// long running handlers can work in bounded steps.
+    pub fn continue_with(&mut self, payload: Values) {
+        self.continuation = Some(payload);
+    }
+
+    /// Takes the payload the command continues with, if any.
+    pub fn take_continuation(&mut self) -> Option<Values> {
+        self.continuation.take()
+    }
+}
+
+/// Returns a slot of the instructed node's input.
+pub(crate) fn input_slot(
+    instruction: &Instruction,
+    input: &InputReference,
+) -> Result<SlotIndex, EngineError> {
+    let context = &instruction.context;
+    context
+        .plan
+        .get_slot_index(context.node, &input.property.id)
+        .ok_or_else(|| {
+            EngineError::from(format!(
+                "Node '{}' has no input '{}'.",
+                context.plan.nodes[context.node].key, input.property.id
+            ))
+        })
+}
+
+/// Returns an index of the instructed node's event after checking its payload.
+pub(crate) fn event_index(
+    instruction: &Instruction,
+    event: &EventReference,
+    payload: &Values,
+) -> Result<EventIndex, EngineError> {
+    let context = &instruction.context;
+    let index = context
+        .plan
+        .get_event_index(context.node, &event.property.id)
+        .ok_or_else(|| {
+            EngineError::from(format!(
+                "Node '{}' has no event '{}'.",
+                context.plan.nodes[context.node].key, event.property.id
+            ))
+        })?;
+    for field in event.property.payload.iter() {
+        match payload.get(&field.id) {
+            Some(value) if value.data_type() == field.data_type => {}
+            _ => {
+                return Err(EngineError::from(format!(
+                    "Event '{}' requires {:?} field '{}'.",
+                    event.property.id, field.data_type, field.id
+                )));
+            }
+        }
+    }
+    Ok(index)
+}
+
+/// Fails if the instructed execution exceeded its wall time, or would exceed its max steps
+/// by dispatching a number of pending instructions.
+pub(crate) fn check_limits(instruction: &Instruction, pending: usize) -> Result<(), EngineError> {
+    let execution = &instruction.context.execution;
+    execution.check_deadline()?;
+    execution.check_steps(pending)
+}
+
+/// Traces a resolved input of the instructed node.
+pub(crate) fn trace_input(
+    tracer: &Tracer,
+    instruction: &Instruction,
+    input: &InputReference,
+    value: &Value,
+) {
+    let context = &instruction.context;
+    tracer.record(context.execution.id(), || TraceEvent::InputResolved {
+        node: context.plan.nodes[context.node].key.clone(),
+        input: input.property.id.clone(),
+        value: value.clone(),
+    });
+}
+
+/// Stores a handler of a command in a table indexed by command keys. Returns the handler it
+/// replaced, if any.
+pub(crate) fn route<T>(
+    handlers: &mut Vec<Option<T>>,
+    command: &CommandReference,
+    handler: T,
+) -> Option<T> {
+    if handlers.len() <= command.key {
+        handlers.resize_with(command.key + 1, || None);
     }
+    handlers[command.key].replace(handler)
 }
@@ -0,0 +1,47 @@
+//! Processor for `printer` nodes.
+
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use crate::error::EngineError;
+use crate::library::basic::printer;
+use crate::library::Library;
+use crate::processor::{Processor, Router};
+
+/// Handles commands of `printer` nodes.
+pub struct PrinterProcessor {
+    router: Router,
+}
+
+impl PrinterProcessor {
+    /// Constructs a `PrinterProcessor` that prints to standard output.
+    pub fn new(lib: &Arc<Library>) -> Result<Self, EngineError> {
+        PrinterProcessor::with_writer(lib, Arc::new(Mutex::new(io::stdout())))
+    }
+
+    /// Constructs a `PrinterProcessor` that prints to a writer.
+    pub fn with_writer(
+        lib: &Arc<Library>,
+        writer: Arc<Mutex<dyn Write + Send>>,
+    ) -> Result<Self, EngineError> {
+        let print_command = lib.get_command(printer::ID, printer::COMMAND_PRINT)?;
+        let content_input = lib.get_input(printer::ID, printer::INPUT_CONTENT)?;
+
+        let mut router = Router::new();
+        router.route(&print_command, move |invocation| {
+            let content = invocation.input(&content_input)?;
+            let mut writer = writer
+                .lock()
+                .map_err(|_| EngineError::with_code("poisoned", "Printer's writer is poisoned."))?;
+            writeln!(writer, "{}", content)
+                .map_err(|e| EngineError::with_code("io", &format!("Failed to print: {}", e)))
+        });
+        Ok(PrinterProcessor { router })
+    }
+}
+
+impl Processor for PrinterProcessor {
+    fn router(&self) -> &Router {
+        &self.router
+    }
+}
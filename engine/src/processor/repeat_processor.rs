@@ -0,0 +1,106 @@
+//! Processor for `repeat` nodes.
+
+use std::sync::Arc;
+
+use graph::value::Value;
+
+use crate::error::EngineError;
+use crate::library::basic::repeat;
+use crate::library::{Library, Values};
+use crate::processor::{Processor, Router};
+
+/// Handles commands of `repeat` nodes.
+pub struct RepeatProcessor {
+    router: Router,
+}
+
+impl RepeatProcessor {
+    /// Constructs a `RepeatProcessor`.
+    pub fn new(lib: &Arc<Library>) -> Result<Self, EngineError> {
+        let start_command = lib.get_command(repeat::ID, repeat::COMMAND_START)?;
+        let executed_event = lib.get_event(repeat::ID, repeat::EVENT_EXECUTED)?;
+        let times_input = lib.get_input(repeat::ID, repeat::INPUT_TIMES)?;
+
+        let mut router = Router::new();
+        router.route(&start_command, move |invocation| {
+            let (id, first, times) = match invocation.payload().get(repeat::FIELD_LOOP) {
+                Some(Value::Integer(id)) => {
+                    let id = *id;
+                    let state = invocation.state();
+                    match (
+                        state.get(&repeat::state_next(id)),
+                        state.get(&repeat::state_times(id)),
+                    ) {
+                        (Some(Value::Integer(next)), Some(Value::Integer(times))) => {
+                            (id, *next, *times)
+                        }
+                        _ => {
+                            return Err(EngineError::from(format!(
+                                "Loop {} of a repeat node has no state.",
+                                id
+                            )))
+                        }
+                    }
+                }
+                _ => match invocation.input(&times_input)? {
+                    Value::Integer(times) => {
+                        let id = invocation.update_state(|state| {
+                            let loops = match state.get(repeat::STATE_LOOPS) {
+                                Some(Value::Integer(loops)) => *loops,
+                                _ => 0,
+                            };
+                            state.insert(repeat::STATE_LOOPS.into(), Value::from(loops + 1));
+                            loops
+                        });
+                        (id, 0, times)
+                    }
+                    value => {
+                        return Err(EngineError::from(format!(
+                            "Input 'times' is not an integer: {:?}",
+                            value
+                        )))
+                    }
+                },
+            };
+
+            let last = times.min(first.saturating_add(repeat::BATCH_SIZE));
+            for iteration in first..last {
+                if invocation.is_cancelled() {
+                    return Ok(());
+                }
+                invocation.check_limits()?;
+                let mut payload = Values::new();
+                payload.insert(repeat::FIELD_ITERATION.into(), Value::from(iteration));
+                invocation.emit(&executed_event, payload)?;
+            }
+            let executed = invocation.events().len() as i64;
+            invocation.update_state(|state| {
+                let total = match state.get(repeat::STATE_EXECUTED) {
+                    Some(Value::Integer(total)) => *total,
+                    _ => 0,
+                };
+                state.insert(repeat::STATE_EXECUTED.into(), Value::from(total + executed));
+                if last < times {
+                    state.insert(repeat::state_next(id), Value::from(last));
+                    state.insert(repeat::state_times(id), Value::from(times));
+                } else {
+                    state.remove(&repeat::state_next(id));
+                    state.remove(&repeat::state_times(id));
+                }
+            });
+            if last < times {
+                let mut payload = invocation.payload().clone();
+                payload.insert(repeat::FIELD_LOOP.into(), Value::from(id));
+                invocation.continue_with(payload);
+            }
+            Ok(())
+        });
+        Ok(RepeatProcessor { router })
+    }
+}
+
+impl Processor for RepeatProcessor {
+    fn router(&self) -> &Router {
+        &self.router
+    }
+}
@@ -0,0 +1,344 @@
+//! Recording of executions and their replay.
+
+use std::fs;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+use serde_json::json;
+
+use crate::error::EngineError;
+use crate::json::{get_array, get_string, get_u64, values_from_json, values_to_json};
+use crate::library::Values;
+use crate::message::Instruction;
+
+/// Index of a handled instruction within a recording.
+pub type Step = usize;
+
+/// Command of a node with a payload.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedInstruction {
+    /// Key of the node.
+    pub node: String,
+    /// Id of the command.
+    pub command: String,
+    /// Payload of the instruction.
+    pub payload: Values,
+}
+
+/// Outputs computed by a non-deterministic node while handling an instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedValues {
+    /// Step during which outputs were computed.
+    pub step: Step,
+    /// Key of the node.
+    pub node: String,
+    /// Computed outputs.
+    pub outputs: Values,
+}
+
+/// Everything needed to reproduce an execution of a graph.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Recording {
+    /// Instructions that started the execution.
+    pub triggers: Vec<RecordedInstruction>,
+    /// Handled instructions, in order they were handled.
+    pub dispatches: Vec<RecordedInstruction>,
+    /// Outputs of non-deterministic nodes.
+    pub values: Vec<RecordedValues>,
+}
+
+/// Records an execution, or feeds a recording back to it when replaying.
+#[derive(Debug)]
+pub struct Recorder {
+    mode: Mode,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Record,
+    Replay,
+}
+
+#[derive(Debug)]
+struct State {
+    recording: Recording,
+    next_step: Step,
+    consumed: Vec<bool>,
+    previewed: Vec<RecordedValues>,
+}
+
+impl RecordedInstruction {
+    /// Constructs a `RecordedInstruction` describing an instruction.
+    pub fn new(instruction: &Instruction) -> Self {
+        let plan = &instruction.context.plan;
+        let command = &plan.commands[instruction.command];
+        RecordedInstruction {
+            node: plan.nodes[command.node].key.clone(),
+            command: command.reference.property.id.clone(),
+            payload: instruction.payload.clone(),
+        }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "node": self.node,
+            "command": self.command,
+            "payload": values_to_json(&self.payload),
+        })
+    }
+
+    fn from_json(json: &serde_json::Value) -> Result<Self, EngineError> {
+        Ok(RecordedInstruction {
+            node: get_string(json, "node", "recording")?,
+            command: get_string(json, "command", "recording")?,
+            payload: values_from_json(&json["payload"])?,
+        })
+    }
+}
+
+impl Recording {
+    /// Saves a recording to a JSON file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), EngineError> {
+        let json = json!({
+            "triggers": self.triggers.iter().map(RecordedInstruction::to_json).collect::<Vec<_>>(),
+            "dispatches": self.dispatches.iter().map(RecordedInstruction::to_json).collect::<Vec<_>>(),
+            "values": self.values.iter().map(|values| json!({
+                "step": values.step,
+                "node": values.node,
+                "outputs": values_to_json(&values.outputs),
+            })).collect::<Vec<_>>(),
+        });
+        fs::write(path.as_ref(), json.to_string()).map_err(|e| {
+            EngineError::from(format!(
+                "Failed to save recording to '{}': {}",
+                path.as_ref().display(),
+                e
+            ))
+        })
+    }
+
+    /// Loads a recording from a JSON file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, EngineError> {
+        let content = fs::read_to_string(path.as_ref()).map_err(|e| {
+            EngineError::from(format!(
+                "Failed to load recording from '{}': {}",
+                path.as_ref().display(),
+                e
+            ))
+        })?;
+        let json: serde_json::Value = serde_json::from_str(&content)
+            .map_err(|e| EngineError::from(format!("Invalid recording: {}", e)))?;
+
+        let mut recording = Recording::default();
+        for trigger in get_array(&json, "triggers", "recording")? {
+            recording
+                .triggers
+                .push(RecordedInstruction::from_json(trigger)?);
+        }
+        for dispatch in get_array(&json, "dispatches", "recording")? {
+            recording
+                .dispatches
+                .push(RecordedInstruction::from_json(dispatch)?);
+        }
+        for values in get_array(&json, "values", "recording")? {
+            recording.values.push(RecordedValues {
+                step: get_u64(values, "step", "recording")? as Step,
+                node: get_string(values, "node", "recording")?,
+                outputs: values_from_json(&values["outputs"])?,
+            });
+        }
+        Ok(recording)
+    }
+}
+
+impl Recorder {
+    /// Constructs a `Recorder` that records an execution.
+    pub fn record() -> Self {
+        Recorder::new(Mode::Record, Recording::default())
+    }
+
+    /// Constructs a `Recorder` that replays a recording.
+    pub fn replay(recording: Recording) -> Self {
+        Recorder::new(Mode::Replay, recording)
+    }
+
+    fn new(mode: Mode, recording: Recording) -> Self {
+        let consumed = vec![false; recording.values.len()];
+        Recorder {
+            mode,
+            state: Mutex::new(State {
+                recording,
+                next_step: 0,
+                consumed,
+                previewed: Vec::new(),
+            }),
+        }
+    }
+
+    /// Returns whether a recording is being replayed.
+    pub fn is_replaying(&self) -> bool {
+        self.mode == Mode::Replay
+    }
+
+    /// Returns what has been recorded so far, or the recording being replayed.
+    pub fn recording(&self) -> Recording {
+        self.lock().recording.clone()
+    }
+
+    /// Records an instruction that starts the execution.
+    pub fn triggered(&self, instruction: &Instruction) {
+        if self.mode == Mode::Record {
+            self.lock()
+                .recording
+                .triggers
+                .push(RecordedInstruction::new(instruction));
+        }
+    }
+
+    /// Records that an instruction is about to be handled and returns its step.
+    ///
+    /// When replaying, fails if the instruction is not the one handled at this step.
+    pub fn handled(&self, instruction: &Instruction) -> Result<Step, EngineError> {
+        let recorded = RecordedInstruction::new(instruction);
+        let mut state = self.lock();
+        let step = state.next_step;
+        match self.mode {
+            Mode::Record => state.recording.dispatches.push(recorded),
+            Mode::Replay => match state.recording.dispatches.get(step) {
+                Some(expected) if *expected == recorded => {}
+                Some(expected) => {
+                    return Err(EngineError::from(format!(
+                        "Replay diverged at step {}: expected '{}#{}', got '{}#{}'.",
+                        step, expected.node, expected.command, recorded.node, recorded.command
+                    )));
+                }
+                None => {
+                    return Err(EngineError::from(format!(
+                        "Replay diverged at step {}: '{}#{}' was not recorded.",
+                        step, recorded.node, recorded.command
+                    )));
+                }
+            },
+        }
+        state.next_step += 1;
+        Ok(step)
+    }
+
+    /// Returns whether an instruction is the next one to be handled in the recording.
+    pub fn is_next(&self, instruction: &Instruction) -> bool {
+        let state = self.lock();
+        state
+            .recording
+            .dispatches
+            .get(state.next_step)
+            .is_some_and(|expected| *expected == RecordedInstruction::new(instruction))
+    }
+
+    /// Returns the step of the next handled instruction.
+    pub(crate) fn upcoming_step(&self) -> Step {
+        self.lock().next_step
+    }
+
+    /// Returns outputs of a non-deterministic node at a step before the step is handled.
+    ///
+    /// When recording, computed outputs are kept and returned again by `evaluate` at that
+    /// step. When replaying, recorded outputs are returned without consuming them.
+    pub(crate) fn preview<F>(
+        &self,
+        step: Step,
+        node: &str,
+        compute: F,
+    ) -> Result<Values, EngineError>
+    where
+        F: FnOnce() -> Result<Values, EngineError>,
+    {
+        let mut state = self.lock();
+        let state = &mut *state;
+        let found = match self.mode {
+            Mode::Record => state
+                .previewed
+                .iter()
+                .find(|values| values.step == step && values.node == node),
+            Mode::Replay => state
+                .recording
+                .values
+                .iter()
+                .enumerate()
+                .find(|(i, values)| {
+                    !state.consumed[*i] && values.step == step && values.node == node
+                })
+                .map(|(_, values)| values),
+        };
+        if let Some(values) = found {
+            return Ok(values.outputs.clone());
+        }
+        if self.mode == Mode::Replay {
+            return Err(EngineError::from(format!(
+                "No recorded outputs of '{}' at step {}.",
+                node, step
+            )));
+        }
+        let outputs = compute()?;
+        state.previewed.push(RecordedValues {
+            step,
+            node: String::from(node),
+            outputs: outputs.clone(),
+        });
+        Ok(outputs)
+    }
+
+    /// Computes outputs of a non-deterministic node at a step, or takes them from the
+    /// recording when replaying.
+    pub fn evaluate<F>(&self, step: Step, node: &str, compute: F) -> Result<Values, EngineError>
+    where
+        F: FnOnce() -> Result<Values, EngineError>,
+    {
+        match self.mode {
+            Mode::Record => {
+                let previewed = {
+                    let mut state = self.lock();
+                    state
+                        .previewed
+                        .iter()
+                        .position(|values| values.step == step && values.node == node)
+                        .map(|index| state.previewed.remove(index).outputs)
+                };
+                let outputs = match previewed {
+                    Some(outputs) => outputs,
+                    None => compute()?,
+                };
+                self.lock().recording.values.push(RecordedValues {
+                    step,
+                    node: String::from(node),
+                    outputs: outputs.clone(),
+                });
+                Ok(outputs)
+            }
+            Mode::Replay => {
+                let mut state = self.lock();
+                let state = &mut *state;
+                let index = state
+                    .recording
+                    .values
+                    .iter()
+                    .enumerate()
+                    .position(|(i, values)| {
+                        !state.consumed[i] && values.step == step && values.node == node
+                    })
+                    .ok_or_else(|| {
+                        EngineError::from(format!(
+                            "No recorded outputs of '{}' at step {}.",
+                            node, step
+                        ))
+                    })?;
+                state.consumed[index] = true;
+                Ok(state.recording.values[index].outputs.clone())
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
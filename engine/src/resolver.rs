@@ -0,0 +1,137 @@
+//! Resolution of values needed while executing commands.
+
+use std::sync::Arc;
+
+use graph::value::Value;
+
+use crate::error::EngineError;
+use crate::library::Values;
+use crate::plan::{ExecutionPlan, NodeIndex, SlotIndex, SlotSource};
+use crate::record::{Recorder, Step};
+
+/// Resolves values of slots by walking data edges upstream and evaluating pure nodes.
+///
+/// Every output is computed at most once during resolver's lifetime, so nodes
+/// shared by several downstream inputs are not recomputed.
+pub struct Resolver<'a> {
+    plan: &'a ExecutionPlan,
+    outputs: Vec<Option<Value>>,
+    visiting: Vec<NodeIndex>,
+    evaluations: usize,
+    tape: Option<(Arc<Recorder>, Step)>,
+    preview: bool,
+}
+
+impl<'a> Resolver<'a> {
+    /// Constructs a `Resolver` with an empty cache.
+    pub fn new(plan: &'a ExecutionPlan) -> Self {
+        Resolver {
+            plan,
+            outputs: vec![None; plan.slots.len()],
+            visiting: Vec::new(),
+            evaluations: 0,
+            tape: None,
+            preview: false,
+        }
+    }
+
+    /// Shows outputs of non-deterministic nodes that a step not handled yet will see.
+    pub(crate) fn set_preview_tape(&mut self, recorder: Arc<Recorder>, step: Step) {
+        self.tape = Some((recorder, step));
+        self.preview = true;
+    }
+
+    /// Constructs a `Resolver` with outputs cached by an earlier resolver of the same plan.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn with_outputs(plan: &'a ExecutionPlan, outputs: Vec<Option<Value>>) -> Self {
+        Resolver {
+            outputs,
+            ..Resolver::new(plan)
+        }
+    }
+
+    /// Returns cached outputs, to be reused by a later resolver of the same plan.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn into_outputs(self) -> Vec<Option<Value>> {
+        self.outputs
+    }
+
+    /// Records outputs of non-deterministic nodes computed during a step, or takes them
+    /// from the recorder when replaying.
+    pub fn set_tape(&mut self, recorder: Arc<Recorder>, step: Step) {
+        self.tape = Some((recorder, step));
+    }
+
+    /// Returns a value of a slot.
+    pub fn resolve(&mut self, slot: SlotIndex) -> Result<Value, EngineError> {
+        let plan = self.plan;
+        match &plan.slots[slot].source {
+            SlotSource::Constant(value) => Ok(value.clone()),
+            SlotSource::Link(source) => self.resolve(*source),
+            SlotSource::Output => {
+                if self.outputs[slot].is_none() {
+                    self.evaluate(plan.slots[slot].node)?;
+                }
+                self.outputs[slot].clone().ok_or_else(|| {
+                    EngineError::from(format!(
+                        "Node '{}' did not compute '{}'.",
+                        plan.nodes[plan.slots[slot].node].key, plan.slots[slot].property_id
+                    ))
+                })
+            }
+        }
+    }
+
+    /// Returns how many times node functions were called.
+    pub fn evaluations(&self) -> usize {
+        self.evaluations
+    }
+
+    fn evaluate(&mut self, node: NodeIndex) -> Result<(), EngineError> {
+        let plan = self.plan;
+        let plan_node = &plan.nodes[node];
+        let function = plan_node.function.ok_or_else(|| {
+            EngineError::from(format!(
+                "Outputs of '{}' are not computed by a pure node.",
+                plan_node.key
+            ))
+        })?;
+        if self.visiting.contains(&node) {
+            return Err(EngineError::from(format!(
+                "Data cycle through '{}'.",
+                plan_node.key
+            )));
+        }
+
+        self.visiting.push(node);
+        let mut inputs = Values::new();
+        for input in plan_node.inputs.iter() {
+            match self.resolve(*input) {
+                Ok(value) => {
+                    inputs.insert(plan.slots[*input].property_id.clone(), value);
+                }
+                Err(e) => {
+                    self.visiting.pop();
+                    return Err(e);
+                }
+            }
+        }
+        self.visiting.pop();
+
+        self.evaluations += 1;
+        let mut outputs = match &self.tape {
+            Some((recorder, step)) if !plan_node.deterministic && self.preview => {
+                recorder.preview(*step, &plan_node.key, || function(&inputs))
+            }
+            Some((recorder, step)) if !plan_node.deterministic => {
+                recorder.evaluate(*step, &plan_node.key, || function(&inputs))
+            }
+            _ => function(&inputs),
+        }
+        .map_err(|e| EngineError::from(format!("'{}': {}", plan_node.key, e.message)))?;
+        for output in plan_node.outputs.iter() {
+            self.outputs[*output] = outputs.remove(&plan.slots[*output].property_id);
+        }
+        Ok(())
+    }
+}
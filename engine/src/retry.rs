@@ -0,0 +1,62 @@
+//! Policies for retrying failed commands.
+
+use std::time::Duration;
+
+use serde_json::json;
+
+pub use graph::retry::{Backoff, RetryPolicy};
+
+use crate::error::EngineError;
+use crate::json::{get_f64, get_string, get_u64};
+
+/// Returns a retry policy as a JSON object.
+pub(crate) fn to_json(policy: &RetryPolicy) -> serde_json::Value {
+    let backoff = match policy.backoff() {
+        Backoff::Fixed(delay) => json!({
+            "type": "fixed",
+            "delay_ms": delay.as_millis() as u64,
+        }),
+        Backoff::Exponential { initial, max } => json!({
+            "type": "exponential",
+            "initial_ms": initial.as_millis() as u64,
+            "max_ms": max.as_millis() as u64,
+        }),
+    };
+    json!({
+        "max_attempts": policy.max_attempts(),
+        "backoff": backoff,
+        "jitter": policy.jitter_fraction(),
+        "retry_on": policy.retried_codes(),
+    })
+}
+
+/// Reads a retry policy from a JSON object.
+pub(crate) fn from_json(json: &serde_json::Value) -> Result<RetryPolicy, EngineError> {
+    let ms = |json: &serde_json::Value, id: &str| {
+        Ok::<_, EngineError>(Duration::from_millis(get_u64(json, id, "retry policy")?))
+    };
+    let policy = RetryPolicy::new(get_u64(json, "max_attempts", "retry policy")? as u32)
+        .jitter(get_f64(json, "jitter", "retry policy")?);
+    let backoff = &json["backoff"];
+    let policy = match get_string(backoff, "type", "retry policy")?.as_str() {
+        "fixed" => policy.fixed(ms(backoff, "delay_ms")?),
+        "exponential" => policy.exponential(ms(backoff, "initial_ms")?, ms(backoff, "max_ms")?),
+        backoff => {
+            return Err(EngineError::from(format!(
+                "Invalid retry policy: unknown backoff '{}'.",
+                backoff
+            )))
+        }
+    };
+    match &json["retry_on"] {
+        serde_json::Value::Null => Ok(policy),
+        serde_json::Value::Array(codes) => codes.iter().try_fold(policy, |policy, code| {
+            code.as_str()
+                .map(|code| policy.retry_on(code))
+                .ok_or_else(|| EngineError::new("Invalid retry policy: invalid 'retry_on'."))
+        }),
+        _ => Err(EngineError::new(
+            "Invalid retry policy: invalid 'retry_on'.",
+        )),
+    }
+}
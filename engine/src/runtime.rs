@@ -0,0 +1,436 @@
+//! Asynchronous backend that runs instructions as tasks on a tokio runtime.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::Poll;
+use std::time::Instant;
+
+use tokio::runtime::Handle;
+
+use graph::graph::Graph;
+use graph::value::Value;
+
+use crate::dispatcher::{self, Dispatcher, InFlight};
+use crate::error::EngineError;
+use crate::execution::{CancellationToken, Execution, ExecutionHandle, ExecutionOptions};
+use crate::library::{CommandReference, EventReference, InputReference, Library, Values};
+use crate::message::Instruction;
+use crate::metrics::{Metrics, MetricsSink};
+use crate::plan::{CommandIndex, EventIndex, ExecutionPlan, PlanNode};
+use crate::processor::{self, Processor};
+use crate::record::{Recorder, Step};
+use crate::resolver::Resolver;
+use crate::trace::{TraceEvent, TraceSink, Tracer};
+
+/// Boxed future that can be sent between threads.
+pub type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send + 'static>>;
+
+/// Handles a command of a node asynchronously, returning the invocation with fired events.
+pub type AsyncHandler =
+    Box<dyn Fn(AsyncInvocation) -> BoxFuture<Result<AsyncInvocation, EngineError>> + Send + Sync>;
+
+/// Routes instructions to asynchronous handlers by keys of their commands.
+#[derive(Default)]
+pub struct AsyncRouter {
+    handlers: Vec<Option<AsyncHandler>>,
+}
+
+/// Gives an asynchronous handler access to the instruction being executed.
+///
+/// Unlike `Invocation` it owns the instruction, so it can be held across `.await`.
+#[derive(Debug)]
+pub struct AsyncInvocation {
+    instruction: Arc<Instruction>,
+    events: Vec<(EventIndex, Values)>,
+    targets: usize,
+    tracer: Tracer,
+    tape: Option<(Arc<Recorder>, Step)>,
+    outputs: Mutex<Vec<Option<Value>>>,
+}
+
+/// Runs graphs by spawning every instruction as a task on a tokio runtime.
+///
+/// Commands with an asynchronous handler are handled by it, others by synchronous processors.
+pub struct AsyncEngine {
+    library: Arc<Library>,
+    shared: Arc<Shared>,
+    next_execution_id: AtomicU64,
+}
+
+struct Shared {
+    dispatcher: Dispatcher,
+    router: AsyncRouter,
+    runtime: Handle,
+}
+
+impl AsyncRouter {
+    /// Constructs an `AsyncRouter`.
+    pub fn new() -> Self {
+        AsyncRouter {
+            handlers: Vec::new(),
+        }
+    }
+
+    /// Registers an asynchronous handler for a command.
+    /// # Panics
+    /// If command already has a handler.
+    pub fn route<F, T>(&mut self, command: &CommandReference, handler: F)
+    where
+        F: Fn(AsyncInvocation) -> T + Send + Sync + 'static,
+        T: Future<Output = Result<AsyncInvocation, EngineError>> + Send + 'static,
+    {
+        let handler: AsyncHandler = Box::new(move |invocation| Box::pin(handler(invocation)));
+        if processor::route(&mut self.handlers, command, handler).is_some() {
+            panic!(
+                "duplicate handler for '{}#{}'",
+                command.node.id, command.property.id
+            );
+        }
+    }
+
+    /// Returns a handler for an instruction's command.
+    pub fn resolve(&self, instruction: &Instruction) -> Option<&AsyncHandler> {
+        let command = &instruction.context.plan.commands[instruction.command];
+        self.handlers.get(command.reference.key)?.as_ref()
+    }
+}
+
+impl AsyncInvocation {
+    fn new(
+        instruction: Arc<Instruction>,
+        tracer: Tracer,
+        tape: Option<(Arc<Recorder>, Step)>,
+    ) -> Self {
+        AsyncInvocation {
+            instruction,
+            events: Vec::new(),
+            targets: 0,
+            tracer,
+            tape,
+            outputs: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Runs a function with a resolver that keeps its cache for the whole invocation.
+    fn with_resolver<F, T>(&self, f: F) -> T
+    where
+        F: FnOnce(&mut Resolver<'_>) -> T,
+    {
+        let plan = &self.instruction.context.plan;
+        let mut outputs = self.outputs.lock().unwrap_or_else(|e| e.into_inner());
+        let mut resolver = match std::mem::take(&mut *outputs) {
+            cached if cached.is_empty() => Resolver::new(plan),
+            cached => Resolver::with_outputs(plan, cached),
+        };
+        if let Some((recorder, step)) = &self.tape {
+            resolver.set_tape(Arc::clone(recorder), *step);
+        }
+        let result = f(&mut resolver);
+        *outputs = resolver.into_outputs();
+        result
+    }
+
+    /// Returns the instruction being executed.
+    pub fn instruction(&self) -> &Instruction {
+        &self.instruction
+    }
+
+    /// Returns the node being executed.
+    pub fn node(&self) -> &PlanNode {
+        &self.instruction.context.plan.nodes[self.instruction.context.node]
+    }
+
+    /// Returns payload of an event that triggered the command.
+    pub fn payload(&self) -> &Values {
+        &self.instruction.payload
+    }
+
+    /// Fails if the execution exceeded its limits, counting commands targeted by fired
+    /// events as dispatched.
+    pub fn check_limits(&self) -> Result<(), EngineError> {
+        processor::check_limits(&self.instruction, self.targets)
+    }
+
+    /// Returns cancellation token of the execution.
+    pub fn token(&self) -> &CancellationToken {
+        self.instruction.context.execution.token()
+    }
+
+    /// Returns whether the execution was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.token().is_cancelled()
+    }
+
+    /// Returns state the executing node stored in the execution.
+    pub fn state(&self) -> Values {
+        self.instruction
+            .context
+            .execution
+            .node_state(&self.node().key)
+    }
+
+    /// Updates state of the executing node in the execution.
+    pub fn update_state<F, T>(&self, update: F) -> T
+    where
+        F: FnOnce(&mut Values) -> T,
+    {
+        self.instruction
+            .context
+            .execution
+            .update_node_state(&self.node().key, update)
+    }
+
+    /// Returns a value of the executing node's input, computing it from connected nodes if needed.
+    ///
+    /// Outputs of upstream nodes are computed at most once per invocation.
+    pub fn input(&self, input: &InputReference) -> Result<Value, EngineError> {
+        let slot = processor::input_slot(&self.instruction, input)?;
+        let value = self.with_resolver(|resolver| resolver.resolve(slot))?;
+        processor::trace_input(&self.tracer, &self.instruction, input, &value);
+        Ok(value)
+    }
+
+    /// Fires an event of the executing node.
+    pub fn emit(&mut self, event: &EventReference, payload: Values) -> Result<(), EngineError> {
+        let index = processor::event_index(&self.instruction, event, &payload)?;
+        self.targets += self.instruction.context.plan.events[index].targets.len();
+        self.events.push((index, payload));
+        Ok(())
+    }
+
+    /// Returns fired events with their payloads, in order of firing.
+    pub fn events(&self) -> &[(EventIndex, Values)] {
+        &self.events
+    }
+}
+
+impl AsyncEngine {
+    /// Constructs an `AsyncEngine` with processors of the basic library that spawns tasks on
+    /// a runtime.
+    ///
+    /// Retries wait on the runtime's timer, so runtimes of engines with retry delays need
+    /// time enabled.
+    pub fn new(
+        library: Arc<Library>,
+        router: AsyncRouter,
+        runtime: Handle,
+    ) -> Result<Self, EngineError> {
+        let dispatcher = Dispatcher::new(&library)?;
+        Ok(AsyncEngine::with_dispatcher(
+            library, dispatcher, router, runtime,
+        ))
+    }
+
+    /// Constructs an `AsyncEngine` with given synchronous processors.
+    pub fn with_processors(
+        library: Arc<Library>,
+        processors: Vec<Box<dyn Processor>>,
+        router: AsyncRouter,
+        runtime: Handle,
+    ) -> Self {
+        let dispatcher = Dispatcher::with_processors(processors);
+        AsyncEngine::with_dispatcher(library, dispatcher, router, runtime)
+    }
+
+    fn with_dispatcher(
+        library: Arc<Library>,
+        dispatcher: Dispatcher,
+        router: AsyncRouter,
+        runtime: Handle,
+    ) -> Self {
+        AsyncEngine {
+            library,
+            shared: Arc::new(Shared {
+                dispatcher,
+                router,
+                runtime,
+            }),
+            next_execution_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Records trace of executions to a sink.
+    ///
+    /// Fails if tasks of started executions are still running.
+    pub fn set_trace_sink(&mut self, sink: Arc<dyn TraceSink>) -> Result<(), EngineError> {
+        let shared = Arc::get_mut(&mut self.shared).ok_or_else(|| {
+            EngineError::new("Cannot set trace sink while executions are running.")
+        })?;
+        shared.dispatcher.set_tracer(Tracer::new(sink));
+        Ok(())
+    }
+
+    /// Records metrics of executions to a sink.
+    ///
+    /// Fails if tasks of started executions are still running.
+    pub fn set_metrics_sink(&mut self, sink: Arc<dyn MetricsSink>) -> Result<(), EngineError> {
+        let shared = Arc::get_mut(&mut self.shared).ok_or_else(|| {
+            EngineError::new("Cannot set metrics sink while executions are running.")
+        })?;
+        shared.dispatcher.set_metrics(Metrics::new(sink));
+        Ok(())
+    }
+
+    /// Starts executing a graph by triggering a command of a node.
+    ///
+    /// Returns as soon as the command is spawned. Await `ExecutionHandle::completion`
+    /// for the outcome.
+    pub fn execute(
+        &self,
+        graph: Graph,
+        key: &str,
+        command_id: &str,
+    ) -> Result<ExecutionHandle, EngineError> {
+        self.execute_with(graph, key, command_id, ExecutionOptions::default())
+    }
+
+    /// Starts executing a graph by triggering a command of a node with options.
+    pub fn execute_with(
+        &self,
+        graph: Graph,
+        key: &str,
+        command_id: &str,
+        options: ExecutionOptions,
+    ) -> Result<ExecutionHandle, EngineError> {
+        let plan = Arc::new(ExecutionPlan::compile(&graph, &self.library)?);
+        let command = plan.find_command(key, command_id)?;
+        self.start(plan, vec![command], options)
+    }
+
+    /// Starts executing a graph by triggering every `action` node once.
+    pub fn execute_actions(&self, graph: Graph) -> Result<ExecutionHandle, EngineError> {
+        self.execute_actions_with(graph, ExecutionOptions::default())
+    }
+
+    /// Starts executing a graph by triggering every `action` node once with options.
+    pub fn execute_actions_with(
+        &self,
+        graph: Graph,
+        options: ExecutionOptions,
+    ) -> Result<ExecutionHandle, EngineError> {
+        let plan = Arc::new(ExecutionPlan::compile(&graph, &self.library)?);
+        let commands = plan.get_actions();
+        self.start(plan, commands, options)
+    }
+
+    fn start(
+        &self,
+        plan: Arc<ExecutionPlan>,
+        entries: Vec<CommandIndex>,
+        options: ExecutionOptions,
+    ) -> Result<ExecutionHandle, EngineError> {
+        plan.check_entries(&entries)?;
+        let id = self.next_execution_id.fetch_add(1, Ordering::SeqCst);
+        let execution = Arc::new(Execution::with_options(id, plan, options));
+
+        let dispatcher = &self.shared.dispatcher;
+        execution.start(
+            &entries,
+            dispatcher.tracer(),
+            dispatcher.metrics(),
+            |instruction| spawn(&self.shared, instruction),
+        );
+
+        Ok(ExecutionHandle::new(execution, entries))
+    }
+}
+
+fn spawn(shared: &Arc<Shared>, instruction: Instruction) {
+    shared.runtime.spawn(run(Arc::clone(shared), instruction));
+}
+
+fn run(shared: Arc<Shared>, instruction: Instruction) -> BoxFuture<()> {
+    Box::pin(async move {
+        let in_flight = InFlight(Arc::clone(&instruction.context.execution));
+        backoff(&instruction).await;
+        if in_flight.0.is_finished() {
+            return;
+        }
+        let instruction = Arc::new(instruction);
+        let started = Instant::now();
+        let result = execute(&shared, &instruction).await;
+        shared
+            .dispatcher
+            .observe(&instruction, started, result.is_err());
+        if let Err(e) = result {
+            shared.dispatcher.fail(&in_flight.0, e);
+        }
+    })
+}
+
+async fn execute(shared: &Arc<Shared>, instruction: &Arc<Instruction>) -> Result<(), EngineError> {
+    let dispatch = |next| {
+        spawn(shared, next);
+        Ok(())
+    };
+    let handler = match shared.router.resolve(instruction) {
+        Some(handler) => handler,
+        None => return execute_blocking(shared, instruction).await,
+    };
+
+    let tracer = shared.dispatcher.tracer();
+    let execution = &instruction.context.execution;
+    let plan = &instruction.context.plan;
+    let command = &plan.commands[instruction.command];
+    let tape = dispatcher::tape(instruction)?;
+    execution.check_deadline()?;
+    tracer.record(execution.id(), || TraceEvent::command_started(instruction));
+    let invocation = AsyncInvocation::new(Arc::clone(instruction), tracer.clone(), tape.clone());
+    let mut invocation = match handler(invocation).await {
+        Ok(invocation) => invocation,
+        Err(e) => {
+            let error = e.raised_by(
+                &plan.nodes[command.node].key,
+                &command.reference.property.id,
+            );
+            return shared.dispatcher.retry(instruction, error, tape, dispatch);
+        }
+    };
+    tracer.record(execution.id(), || TraceEvent::command_finished(instruction));
+
+    let events = std::mem::take(&mut invocation.events);
+    invocation
+        .with_resolver(|resolver| {
+            shared
+                .dispatcher
+                .follow(instruction, events, |slot| resolver.resolve(slot), dispatch)
+        })
+        .map(|_| ())
+}
+
+/// Waits until an instruction is due or its execution finishes.
+async fn backoff(instruction: &Instruction) {
+    let not_before = match instruction.not_before {
+        Some(not_before) => tokio::time::Instant::from_std(not_before),
+        None => return,
+    };
+    let mut sleep = std::pin::pin!(tokio::time::sleep_until(not_before));
+    let mut completion = instruction.context.execution.completion();
+    std::future::poll_fn(|cx| {
+        if sleep.as_mut().poll(cx).is_ready() || Pin::new(&mut completion).poll(cx).is_ready() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+/// Handles an instruction with synchronous processors on a thread where blocking is allowed.
+async fn execute_blocking(
+    shared: &Arc<Shared>,
+    instruction: &Arc<Instruction>,
+) -> Result<(), EngineError> {
+    let shared = Arc::clone(shared);
+    let instruction = Arc::clone(instruction);
+    tokio::task::spawn_blocking(move || {
+        shared.dispatcher.execute(&instruction, |next| {
+            spawn(&shared, next);
+            Ok(())
+        })
+    })
+    .await
+    .map_err(|_| EngineError::new("Handler panicked."))?
+}
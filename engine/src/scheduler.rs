@@ -0,0 +1,487 @@
+//! Scheduler that queues instructions and hands them to workers, either fairly between
+//! executions or by letting workers steal each other's instructions.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::iter;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard, RwLock};
+use std::time::Instant;
+
+use crossbeam::deque::{Injector, Steal, Stealer, Worker};
+
+use crate::error::EngineError;
+use crate::execution::{ExecutionId, Priority};
+use crate::message::{Instruction, Message};
+
+/// Decides which execution a worker handles an instruction of next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SchedulingPolicy {
+    /// Serves executions of the highest priority with queued instructions, taking turns
+    /// between executions of the same priority.
+    #[default]
+    RoundRobin,
+    /// Serves all executions in turns, giving each `priority + 1` instructions per turn.
+    WeightedFair,
+    /// Queues follow-up instructions on the worker that dispatched them, with idle workers
+    /// stealing from others. Ignores priorities and `max_queued_per_execution` in favour of
+    /// throughput.
+    WorkStealing,
+}
+
+/// Configuration of the scheduler.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct SchedulerConfig {
+    /// Selection of the next execution.
+    #[serde(default)]
+    pub policy: SchedulingPolicy,
+    /// Maximum number of queued instructions of all executions. Starting a new execution
+    /// waits while it is reached, or fails if no workers are running to drain the queue.
+    #[serde(default)]
+    pub max_queued: Option<usize>,
+    /// Maximum number of queued instructions of one execution. Dispatching further
+    /// instructions of the execution waits until workers take some of them.
+    #[serde(default)]
+    pub max_queued_per_execution: Option<usize>,
+}
+
+/// Queues instructions until a worker takes them.
+///
+/// Producers wait while queues are full, but a worker never waits if no other worker is
+/// left to drain the queue, so queues may grow past their limits rather than stall all
+/// workers. Instructions that are not due yet, such as retries backing off, are held aside
+/// until they are and count as queued only from then on.
+#[derive(Debug)]
+pub struct Scheduler {
+    config: SchedulerConfig,
+    state: Mutex<State>,
+    available: Condvar,
+    room: Condvar,
+    workers: AtomicUsize,
+    waiting: AtomicUsize,
+    queued: AtomicUsize,
+    delayed: AtomicUsize,
+    stops: AtomicUsize,
+    sleeping: AtomicUsize,
+    injector: Injector<Instruction>,
+    stealers: RwLock<Vec<Stealer<Instruction>>>,
+}
+
+/// Instructions queued by one worker, which other workers steal once they run out of their
+/// own. Only used with `SchedulingPolicy::WorkStealing`.
+#[derive(Debug)]
+pub struct LocalQueue {
+    deque: Worker<Instruction>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    queues: HashMap<ExecutionId, Queue>,
+    /// Executions with queued instructions in order of their turns, by scheduling level.
+    turns: BTreeMap<Priority, VecDeque<ExecutionId>>,
+    /// Instructions that are not due yet, by time they are due and order they were pushed.
+    delayed: BTreeMap<(Instant, u64), Instruction>,
+    pushed: u64,
+    /// Workers waiting for room in a full queue.
+    waiting_workers: usize,
+}
+
+#[derive(Debug)]
+struct Queue {
+    weight: u32,
+    credit: u32,
+    instructions: VecDeque<Instruction>,
+}
+
+impl Scheduler {
+    /// Constructs an empty `Scheduler`.
+    pub fn new(config: SchedulerConfig) -> Self {
+        Scheduler {
+            config,
+            state: Mutex::new(State::default()),
+            available: Condvar::new(),
+            room: Condvar::new(),
+            workers: AtomicUsize::new(0),
+            waiting: AtomicUsize::new(0),
+            queued: AtomicUsize::new(0),
+            delayed: AtomicUsize::new(0),
+            stops: AtomicUsize::new(0),
+            sleeping: AtomicUsize::new(0),
+            injector: Injector::new(),
+            stealers: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Returns the number of queued instructions.
+    pub fn len(&self) -> usize {
+        self.queued.load(Ordering::SeqCst)
+    }
+
+    /// Returns whether no instructions are queued.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Sets the number of workers taking instructions, waking producers that wait for
+    /// them to drain full queues.
+    pub fn set_workers(&self, workers: usize) {
+        self.workers.store(workers, Ordering::SeqCst);
+        let _state = self.lock();
+        self.room.notify_all();
+    }
+
+    /// Waits until entry instructions of a new execution can be queued within `max_queued`.
+    ///
+    /// Fails if they can't, because no workers are running or there are more of them than
+    /// `max_queued`.
+    pub fn check_capacity(&self, additional: usize) -> Result<(), EngineError> {
+        let max_queued = match self.config.max_queued {
+            Some(max_queued) => max_queued,
+            None => return Ok(()),
+        };
+        let mut state = self.lock();
+        while self.len() + additional > max_queued {
+            if additional > max_queued || self.workers.load(Ordering::SeqCst) == 0 {
+                return Err(EngineError::from(format!(
+                    "Engine is overloaded: more than {} instructions queued.",
+                    max_queued
+                )));
+            }
+            state = self.wait_for_room(state);
+        }
+        Ok(())
+    }
+
+    /// Queues an instruction of an execution, waiting while the execution already has
+    /// `max_queued_per_execution` instructions queued.
+    pub fn push(&self, instruction: Instruction) {
+        self.push_waiting(instruction, false);
+    }
+
+    /// Queues an instruction dispatched by a worker, waiting while the execution already
+    /// has `max_queued_per_execution` instructions queued and another worker is left to
+    /// take them.
+    pub(crate) fn push_from_worker(&self, instruction: Instruction) {
+        self.push_waiting(instruction, true);
+    }
+
+    fn push_waiting(&self, instruction: Instruction, from_worker: bool) {
+        if !instruction.is_due(Instant::now()) {
+            self.delay(instruction);
+            return;
+        }
+        if self.config.policy == SchedulingPolicy::WorkStealing {
+            // Counts the instruction before stealers can see it, so that taking it never
+            // decrements the count below zero.
+            self.queued.fetch_add(1, Ordering::SeqCst);
+            self.injector.push(instruction);
+            self.wake();
+            return;
+        }
+        let mut state = self.lock();
+        if let Some(max_queued) = self.config.max_queued_per_execution {
+            let execution = &instruction.context.execution;
+            // A waiting worker doesn't drain queues, so it leaves at least one other worker
+            // that does.
+            let reserved = usize::from(from_worker);
+            while state.queued(execution.id()) >= max_queued
+                && !execution.is_finished()
+                && state.waiting_workers + reserved < self.workers.load(Ordering::SeqCst)
+            {
+                state.waiting_workers += reserved;
+                state = self.wait_for_room(state);
+                state.waiting_workers -= reserved;
+            }
+        }
+        self.enqueue(&mut state, instruction);
+        self.available.notify_one();
+    }
+
+    /// Waits until a worker takes an instruction or the number of workers changes.
+    fn wait_for_room<'a>(&self, state: MutexGuard<'a, State>) -> MutexGuard<'a, State> {
+        self.waiting.fetch_add(1, Ordering::SeqCst);
+        let state = self.room.wait(state).unwrap_or_else(|e| e.into_inner());
+        self.waiting.fetch_sub(1, Ordering::SeqCst);
+        state
+    }
+
+    /// Wakes up producers waiting for room after a worker took an instruction. Called with
+    /// the state locked, so that producers about to wait don't miss it.
+    fn make_room(&self) {
+        if self.waiting.load(Ordering::SeqCst) > 0 {
+            self.room.notify_all();
+        }
+    }
+
+    fn enqueue(&self, state: &mut State, instruction: Instruction) {
+        let execution = &instruction.context.execution;
+        let id = execution.id();
+        let (level, weight) = if self.config.policy == SchedulingPolicy::RoundRobin {
+            (execution.priority(), 1)
+        } else {
+            (0, u32::from(execution.priority()) + 1)
+        };
+
+        let queue = state.queues.entry(id).or_insert_with(|| Queue {
+            weight,
+            credit: 0,
+            instructions: VecDeque::new(),
+        });
+        if queue.instructions.is_empty() {
+            state.turns.entry(level).or_default().push_back(id);
+        }
+        queue.instructions.push_back(instruction);
+        self.queued.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Holds an instruction aside until it is due, waking workers to wait for it.
+    fn delay(&self, instruction: Instruction) {
+        let not_before = instruction.not_before.unwrap_or_else(Instant::now);
+        let mut state = self.lock();
+        state.pushed += 1;
+        let key = (not_before, state.pushed);
+        state.delayed.insert(key, instruction);
+        self.delayed.fetch_add(1, Ordering::SeqCst);
+        self.available.notify_all();
+    }
+
+    /// Queues delayed instructions that are due, or whose executions have finished so that
+    /// workers drop them. Returns when the next delayed instruction is due.
+    ///
+    /// Delayed instructions are queued past `max_queued_per_execution`, as they were
+    /// admitted when first queued.
+    fn release(&self, state: &mut State) -> Option<Instant> {
+        if self.delayed.load(Ordering::SeqCst) == 0 {
+            return None;
+        }
+        let now = Instant::now();
+        let released: Vec<_> = state
+            .delayed
+            .iter()
+            .filter(|((not_before, _), instruction)| {
+                *not_before <= now || instruction.context.execution.is_finished()
+            })
+            .map(|(key, _)| *key)
+            .collect();
+        for key in released {
+            let instruction = state.delayed.remove(&key)?;
+            self.delayed.fetch_sub(1, Ordering::SeqCst);
+            if self.config.policy == SchedulingPolicy::WorkStealing {
+                self.queued.fetch_add(1, Ordering::SeqCst);
+                self.injector.push(instruction);
+            } else {
+                self.enqueue(state, instruction);
+            }
+        }
+        state
+            .delayed
+            .keys()
+            .next()
+            .map(|(not_before, _)| *not_before)
+    }
+
+    /// Creates a queue for a worker, if workers steal instructions from each other.
+    pub fn local_queue(&self) -> Option<LocalQueue> {
+        if self.config.policy != SchedulingPolicy::WorkStealing {
+            return None;
+        }
+        let deque = Worker::new_fifo();
+        self.stealers
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(deque.stealer());
+        Some(LocalQueue { deque })
+    }
+
+    /// Queues an instruction on a worker's queue.
+    pub fn push_local(&self, local: &LocalQueue, instruction: Instruction) {
+        if !instruction.is_due(Instant::now()) {
+            self.delay(instruction);
+            return;
+        }
+        self.queued.fetch_add(1, Ordering::SeqCst);
+        local.deque.push(instruction);
+        self.wake();
+    }
+
+    /// Asks a number of workers to stop. Stopping takes precedence over queued instructions.
+    pub fn stop(&self, workers: usize) {
+        self.stops.fetch_add(workers, Ordering::SeqCst);
+        let _state = self.lock();
+        self.available.notify_all();
+    }
+
+    /// Discards requests to stop that no worker took, e.g. because it terminated by panic.
+    pub fn clear_stops(&self) {
+        self.stops.store(0, Ordering::SeqCst);
+    }
+
+    /// Takes the next message, blocking until there is one.
+    pub fn pop(&self) -> Message {
+        if self.config.policy == SchedulingPolicy::WorkStealing {
+            return self.pop_stealing(None);
+        }
+        let mut state = self.lock();
+        loop {
+            if self.take_stop() {
+                return Message::Stop;
+            }
+            let due = self.release(&mut state);
+            if let Some(instruction) = self.next(&mut state) {
+                return Message::Instruction(instruction);
+            }
+            state = self.wait(state, due);
+        }
+    }
+
+    /// Takes the next message for a worker with its own queue, blocking until there is one.
+    ///
+    /// Instructions are taken from the worker's queue first, then from instructions of new
+    /// executions and then stolen from other workers.
+    pub fn pop_local(&self, local: &LocalQueue) -> Message {
+        self.pop_stealing(Some(local))
+    }
+
+    /// Takes the next instruction without blocking, if there is one.
+    pub fn try_pop(&self) -> Option<Instruction> {
+        let mut state = self.lock();
+        self.release(&mut state);
+        if self.config.policy == SchedulingPolicy::WorkStealing {
+            drop(state);
+            self.steal(None)
+        } else {
+            self.next(&mut state)
+        }
+    }
+
+    fn pop_stealing(&self, local: Option<&LocalQueue>) -> Message {
+        loop {
+            if self.take_stop() {
+                return Message::Stop;
+            }
+            if self.delayed.load(Ordering::SeqCst) > 0 {
+                self.release(&mut self.lock());
+            }
+            if let Some(instruction) = self.steal(local) {
+                return Message::Instruction(instruction);
+            }
+
+            // Announces sleeping before checking again, so that instructions pushed in
+            // between wake it up.
+            let mut state = self.lock();
+            self.sleeping.fetch_add(1, Ordering::SeqCst);
+            let due = self.release(&mut state);
+            if self.stops.load(Ordering::SeqCst) == 0 && self.is_empty() {
+                drop(self.wait(state, due));
+            }
+            self.sleeping.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Waits until another thread notifies or a delayed instruction is due.
+    fn wait<'a>(
+        &self,
+        state: MutexGuard<'a, State>,
+        due: Option<Instant>,
+    ) -> MutexGuard<'a, State> {
+        match due {
+            Some(due) => {
+                let timeout = due.saturating_duration_since(Instant::now());
+                self.available
+                    .wait_timeout(state, timeout)
+                    .unwrap_or_else(|e| e.into_inner())
+                    .0
+            }
+            None => self
+                .available
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner()),
+        }
+    }
+
+    fn next(&self, state: &mut State) -> Option<Instruction> {
+        let instruction = state.next()?;
+        self.queued.fetch_sub(1, Ordering::SeqCst);
+        self.make_room();
+        Some(instruction)
+    }
+
+    fn steal(&self, local: Option<&LocalQueue>) -> Option<Instruction> {
+        let instruction = match local {
+            Some(local) => local
+                .deque
+                .pop()
+                .or_else(|| self.find(|| self.injector.steal_batch_and_pop(&local.deque))),
+            None => self.find(|| self.injector.steal()),
+        }?;
+        self.queued.fetch_sub(1, Ordering::SeqCst);
+        if self.waiting.load(Ordering::SeqCst) > 0 {
+            let _state = self.lock();
+            self.make_room();
+        }
+        Some(instruction)
+    }
+
+    /// Steals an instruction, first with a function and then from any worker, retrying
+    /// while stealing is contended.
+    fn find(&self, first: impl Fn() -> Steal<Instruction>) -> Option<Instruction> {
+        let stealers = self.stealers.read().unwrap_or_else(|e| e.into_inner());
+        iter::repeat_with(|| first().or_else(|| stealers.iter().map(Stealer::steal).collect()))
+            .find(|steal| !steal.is_retry())
+            .and_then(Steal::success)
+    }
+
+    fn take_stop(&self) -> bool {
+        self.stops
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |stops| {
+                stops.checked_sub(1)
+            })
+            .is_ok()
+    }
+
+    /// Wakes up a sleeping worker, if there is one.
+    fn wake(&self) {
+        if self.sleeping.load(Ordering::SeqCst) > 0 {
+            let _state = self.lock();
+            self.available.notify_one();
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl State {
+    fn queued(&self, id: ExecutionId) -> usize {
+        self.queues
+            .get(&id)
+            .map_or(0, |queue| queue.instructions.len())
+    }
+
+    fn next(&mut self) -> Option<Instruction> {
+        let (&level, turns) = self
+            .turns
+            .iter_mut()
+            .rev()
+            .find(|(_, turns)| !turns.is_empty())?;
+        let id = *turns.front()?;
+        let queue = self.queues.get_mut(&id)?;
+        if queue.credit == 0 {
+            queue.credit = queue.weight;
+        }
+        let instruction = queue.instructions.pop_front()?;
+        queue.credit -= 1;
+
+        if queue.instructions.is_empty() {
+            self.queues.remove(&id);
+            turns.pop_front();
+        } else if queue.credit == 0 {
+            turns.rotate_left(1);
+        }
+        if turns.is_empty() {
+            self.turns.remove(&level);
+        }
+        Some(instruction)
+    }
+}
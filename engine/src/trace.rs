@@ -0,0 +1,307 @@
+//! Structured trace of executions.
+
+use std::fmt::{self, Debug, Formatter};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_json::json;
+
+use graph::value::Value;
+
+use crate::error::EngineError;
+use crate::execution::ExecutionId;
+use crate::json::value_to_json;
+use crate::message::Instruction;
+
+/// Something that happened during an execution.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TraceEvent {
+    /// An instruction was queued for a command.
+    Dispatched {
+        /// Key of the instructed node.
+        node: String,
+        /// Id of the instructed command.
+        command: String,
+    },
+    /// A handler started executing a command.
+    CommandStarted {
+        /// Key of the node.
+        node: String,
+        /// Id of the command.
+        command: String,
+    },
+    /// A handler finished executing a command without an error.
+    CommandFinished {
+        /// Key of the node.
+        node: String,
+        /// Id of the command.
+        command: String,
+    },
+    /// A handler failed and its command will be retried.
+    Retrying {
+        /// Key of the node.
+        node: String,
+        /// Id of the command.
+        command: String,
+        /// Failed attempt, counted from 1.
+        attempt: u32,
+        /// Wait before the next attempt.
+        delay: Duration,
+        /// Error message of the failed attempt.
+        message: String,
+    },
+    /// A handler fired an event.
+    EventFired {
+        /// Key of the node.
+        node: String,
+        /// Id of the event.
+        event: String,
+    },
+    /// An edge from a fired event was followed.
+    EdgeFollowed {
+        /// Edge in `source#event>target#command` form.
+        edge: String,
+    },
+    /// A handler read an input.
+    InputResolved {
+        /// Key of the node.
+        node: String,
+        /// Id of the input.
+        input: String,
+        /// Resolved value.
+        value: Value,
+    },
+    /// Handling of an instruction failed.
+    Error {
+        /// Error message.
+        message: String,
+    },
+}
+
+/// A trace event with the time and place it happened.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceRecord {
+    /// When the event happened.
+    pub timestamp: SystemTime,
+    /// Worker that handled the instruction, if any.
+    pub worker: Option<u64>,
+    /// Execution the event belongs to.
+    pub execution: ExecutionId,
+    /// What happened.
+    pub event: TraceEvent,
+}
+
+/// Collects trace records.
+pub trait TraceSink: Send + Sync {
+    /// Stores a record.
+    fn record(&self, record: TraceRecord);
+}
+
+/// Keeps trace records in memory.
+#[derive(Debug, Default)]
+pub struct MemorySink {
+    records: Mutex<Vec<TraceRecord>>,
+}
+
+/// Streams trace records as JSON lines to a writer.
+pub struct JsonLinesSink {
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+/// Emits trace records to a sink, if there is one.
+#[derive(Clone, Default)]
+pub struct Tracer {
+    sink: Option<Arc<dyn TraceSink>>,
+    worker: Option<u64>,
+}
+
+impl TraceEvent {
+    /// Constructs `TraceEvent::Dispatched` for an instruction.
+    pub fn dispatched(instruction: &Instruction) -> Self {
+        let (node, command) = describe(instruction);
+        TraceEvent::Dispatched { node, command }
+    }
+
+    /// Constructs `TraceEvent::CommandStarted` for an instruction.
+    pub fn command_started(instruction: &Instruction) -> Self {
+        let (node, command) = describe(instruction);
+        TraceEvent::CommandStarted { node, command }
+    }
+
+    /// Constructs `TraceEvent::CommandFinished` for an instruction.
+    pub fn command_finished(instruction: &Instruction) -> Self {
+        let (node, command) = describe(instruction);
+        TraceEvent::CommandFinished { node, command }
+    }
+}
+
+impl TraceRecord {
+    /// Returns the record as a JSON object.
+    pub fn to_json(&self) -> serde_json::Value {
+        let timestamp = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_micros() as u64)
+            .unwrap_or(0);
+        let mut object = json!({
+            "timestamp": timestamp,
+            "worker": self.worker,
+            "execution": self.execution,
+        });
+        let fields = match &self.event {
+            TraceEvent::Dispatched { node, command } => {
+                json!({ "type": "dispatched", "node": node, "command": command })
+            }
+            TraceEvent::CommandStarted { node, command } => {
+                json!({ "type": "command_started", "node": node, "command": command })
+            }
+            TraceEvent::CommandFinished { node, command } => {
+                json!({ "type": "command_finished", "node": node, "command": command })
+            }
+            TraceEvent::Retrying {
+                node,
+                command,
+                attempt,
+                delay,
+                message,
+            } => json!({
+                "type": "retrying",
+                "node": node,
+                "command": command,
+                "attempt": attempt,
+                "delay_ms": delay.as_millis() as u64,
+                "message": message,
+            }),
+            TraceEvent::EventFired { node, event } => {
+                json!({ "type": "event_fired", "node": node, "event": event })
+            }
+            TraceEvent::EdgeFollowed { edge } => json!({ "type": "edge_followed", "edge": edge }),
+            TraceEvent::InputResolved { node, input, value } => json!({
+                "type": "input_resolved",
+                "node": node,
+                "input": input,
+                "value": value_to_json(value),
+            }),
+            TraceEvent::Error { message } => json!({ "type": "error", "message": message }),
+        };
+        if let (Some(object), serde_json::Value::Object(fields)) = (object.as_object_mut(), fields)
+        {
+            object.extend(fields);
+        }
+        object
+    }
+}
+
+impl MemorySink {
+    /// Constructs an empty `MemorySink`.
+    pub fn new() -> Self {
+        MemorySink {
+            records: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Returns collected records in order they were recorded.
+    pub fn records(&self) -> Vec<TraceRecord> {
+        self.records
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+impl TraceSink for MemorySink {
+    fn record(&self, record: TraceRecord) {
+        self.records
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(record);
+    }
+}
+
+impl JsonLinesSink {
+    /// Constructs a `JsonLinesSink` that writes to a writer.
+    pub fn new(writer: Box<dyn Write + Send>) -> Self {
+        JsonLinesSink {
+            writer: Mutex::new(writer),
+        }
+    }
+
+    /// Constructs a `JsonLinesSink` that writes to a newly created file.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, EngineError> {
+        let file = File::create(path.as_ref()).map_err(|e| {
+            EngineError::from(format!(
+                "Failed to create trace file '{}': {}",
+                path.as_ref().display(),
+                e
+            ))
+        })?;
+        Ok(JsonLinesSink::new(Box::new(BufWriter::new(file))))
+    }
+}
+
+impl TraceSink for JsonLinesSink {
+    fn record(&self, record: TraceRecord) {
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        // Tracing must not fail an execution, so write errors are ignored.
+        let _ = writeln!(writer, "{}", record.to_json()).and_then(|_| writer.flush());
+    }
+}
+
+impl Debug for Tracer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tracer")
+            .field("enabled", &self.is_enabled())
+            .field("worker", &self.worker)
+            .finish()
+    }
+}
+
+impl Tracer {
+    /// Constructs a `Tracer` that records to a sink.
+    pub fn new(sink: Arc<dyn TraceSink>) -> Self {
+        Tracer {
+            sink: Some(sink),
+            worker: None,
+        }
+    }
+
+    /// Returns a `Tracer` to the same sink that marks records with a worker id.
+    pub fn for_worker(&self, worker: u64) -> Self {
+        Tracer {
+            sink: self.sink.clone(),
+            worker: Some(worker),
+        }
+    }
+
+    /// Returns whether records are collected.
+    pub fn is_enabled(&self) -> bool {
+        self.sink.is_some()
+    }
+
+    /// Records an event of an execution. Event is only constructed if tracing is enabled.
+    pub fn record<F>(&self, execution: ExecutionId, event: F)
+    where
+        F: FnOnce() -> TraceEvent,
+    {
+        if let Some(sink) = &self.sink {
+            sink.record(TraceRecord {
+                timestamp: SystemTime::now(),
+                worker: self.worker,
+                execution,
+                event: event(),
+            });
+        }
+    }
+}
+
+fn describe(instruction: &Instruction) -> (String, String) {
+    let plan = &instruction.context.plan;
+    let command = &plan.commands[instruction.command];
+    (
+        plan.nodes[command.node].key.clone(),
+        command.reference.property.id.clone(),
+    )
+}
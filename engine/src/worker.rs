@@ -1,53 +1,135 @@
-use std::sync::Weak;
+//! Workers execute instructions.
 
-use crossbeam::channel::{Receiver, Sender};
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
 
+use crate::dispatcher::Dispatcher;
+use crate::error::EngineError;
 use crate::library::Library;
 use crate::message::{Instruction, Message};
-use crate::processor::action_processor::ActionProcessor;
-use crate::processor::Processor;
+use crate::metrics::{Metrics, Sample};
+use crate::scheduler::{LocalQueue, Scheduler};
+use crate::trace::{TraceEvent, Tracer};
 
+/// Takes messages from a scheduler and handles them with registered processors.
 pub struct Worker {
     id: u64,
-    outbox: Sender<Message>,
-    inbox: Receiver<Message>,
+    scheduler: Arc<Scheduler>,
+    local: Option<LocalQueue>,
     library: Weak<Library>,
-    processors: Vec<Box<dyn Processor>>,
+    tracer: Tracer,
+    metrics: Metrics,
+    dispatcher: Option<Dispatcher>,
 }
 
 impl Worker {
+    /// Constructs a `Worker`.
     pub fn new(
         id: u64,
-        outbox: Sender<Message>,
-        inbox: Receiver<Message>,
+        scheduler: Arc<Scheduler>,
         library: Weak<Library>,
+        tracer: Tracer,
+        metrics: Metrics,
     ) -> Self {
         Worker {
             id,
-            outbox,
-            inbox,
+            local: scheduler.local_queue(),
+            scheduler,
             library,
-            processors: Vec::new(),
+            tracer: tracer.for_worker(id),
+            metrics,
+            dispatcher: None,
         }
     }
 
+    /// Returns worker's id.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Runs a worker until it receives `Message::Stop`.
     pub fn run(&mut self) {
-        self.register_processors();
+        if let Err(e) = self.register_processors() {
+            eprintln!("[{}] Failed to register processors: {}", self.id, e);
+            return;
+        }
 
-        for message in self.inbox.iter() {
+        let started = Instant::now();
+        let mut busy = Duration::default();
+        loop {
+            let message = match &self.local {
+                Some(local) => self.scheduler.pop_local(local),
+                None => self.scheduler.pop(),
+            };
+            self.metrics
+                .record(|| Sample::QueueDepth(self.scheduler.len()));
             match message {
-                Message::Instruction(instruction) => self.handle_instruction(instruction),
+                Message::Instruction(instruction) => {
+                    let handling = Instant::now();
+                    self.handle_instruction(instruction);
+                    busy += handling.elapsed();
+                    self.metrics.record(|| Sample::WorkerUtilization {
+                        worker: self.id,
+                        ratio: busy.as_secs_f64() / started.elapsed().as_secs_f64(),
+                    });
+                }
+                Message::Stop => break,
             }
         }
     }
 
-    fn register_processors(&mut self) {
-        let library = self.library.upgrade().unwrap();
-        self.processors
-            .push(Box::new(ActionProcessor::new(&library)));
+    /// Registers processors of the basic library, unless they are already registered.
+    fn register_processors(&mut self) -> Result<(), EngineError> {
+        let library = self
+            .library
+            .upgrade()
+            .ok_or_else(|| EngineError::new("Library is no longer available."))?;
+        if self.dispatcher.is_none() {
+            let mut dispatcher = Dispatcher::new(&library)?;
+            dispatcher.set_tracer(self.tracer.clone());
+            dispatcher.set_metrics(self.metrics.clone());
+            self.dispatcher = Some(dispatcher);
+        }
+        Ok(())
     }
 
+    /// Handles an instruction, tracing a panic of its handler before the worker unwinds.
     fn handle_instruction(&self, instruction: Instruction) {
-        println!("[{}] Handling {:?}...", self.id, instruction.command_id);
+        let execution = instruction.context.execution.id();
+        let handled = panic::catch_unwind(AssertUnwindSafe(|| self.dispatch(instruction)));
+        if let Err(e) = handled {
+            self.tracer.record(execution, || TraceEvent::Error {
+                message: format!("Worker {} panicked: {}", self.id, panic_message(&*e)),
+            });
+            panic::resume_unwind(e);
+        }
+    }
+
+    fn dispatch(&self, instruction: Instruction) {
+        let scheduler = &self.scheduler;
+        let local = self.local.as_ref();
+        if let Some(dispatcher) = &self.dispatcher {
+            dispatcher.handle(instruction, |next| match local {
+                Some(local) => {
+                    scheduler.push_local(local, next);
+                    Ok(())
+                }
+                None => {
+                    scheduler.push_from_worker(next);
+                    Ok(())
+                }
+            });
+        }
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => payload
+            .downcast_ref::<String>()
+            .map_or("unknown cause", String::as_str),
     }
 }
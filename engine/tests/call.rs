@@ -0,0 +1,53 @@
+use engine::library::{Library, Values};
+use engine::{Engine, EngineConfig};
+use graph::graph::Graph;
+use graph::value::Value;
+
+#[test]
+fn call() {
+    let library = Library::get();
+    let graph = {
+        let mut gb = Graph::builder(&library.schema);
+        let four = gb.node("integer", "four").unwrap();
+        let plus = gb.node("plus", "plus").unwrap();
+        gb.assign(&four, "value", Value::Integer(4)).unwrap();
+        gb.connect(&four, "return-value", &plus, "b").unwrap();
+        gb.parameter("x", &plus, "a").unwrap();
+        gb.result("sum", &plus, "c").unwrap();
+        gb.build().unwrap()
+    };
+    let engine = Engine::new(EngineConfig::load().unwrap(), library);
+
+    let mut arguments = Values::new();
+    arguments.insert(String::from("x"), Value::Integer(6));
+    let results = engine.call(graph.clone(), &arguments).unwrap();
+    assert_eq!(results.get("sum"), Some(&Value::Integer(10)));
+
+    assert!(engine.call(graph.clone(), &Values::new()).is_err());
+    arguments.insert(String::from("x"), Value::from("6"));
+    assert!(engine.call(graph, &arguments).is_err());
+}
+
+#[test]
+fn call_waits_for_completion() {
+    let library = Library::get();
+    let mut graph = Graph::builder(&library.schema);
+    let a1 = graph.node("action", "a1").unwrap();
+    let r1 = graph.node("repeat", "r1").unwrap();
+    let plus = graph.node("plus", "plus").unwrap();
+    graph.assign(&r1, "times", Value::Integer(50)).unwrap();
+    graph.connect(&a1, "triggered", &r1, "start").unwrap();
+    graph.parameter("x", &plus, "a").unwrap();
+    graph.parameter("y", &plus, "b").unwrap();
+    graph.result("sum", &plus, "c").unwrap();
+    let graph = graph.build().unwrap();
+    let mut engine = Engine::new(EngineConfig::load().unwrap(), library);
+
+    engine.run();
+    let mut arguments = Values::new();
+    arguments.insert(String::from("x"), Value::Integer(6));
+    arguments.insert(String::from("y"), Value::Integer(4));
+    let results = engine.call(graph, &arguments).unwrap();
+    assert_eq!(results.get("sum"), Some(&Value::Integer(10)));
+    assert!(engine.executions().is_empty());
+}
@@ -0,0 +1,335 @@
+use std::collections::HashMap;
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use graph::graph::Graph;
+use graph::schema::Schema;
+use graph::value::Value;
+
+use engine::checkpoint::{Checkpoint, CheckpointStore, FileStore, PendingInstruction};
+use engine::error::EngineError;
+use engine::execution::{ExecutionId, ExecutionLimits, Outcome};
+use engine::library::{Library, Values};
+use engine::retry::RetryPolicy;
+use engine::{Engine, EngineConfig};
+
+mod common;
+
+/// Counts saved graphs and checkpoints of a file store, optionally failing removals.
+struct CountingStore {
+    store: FileStore,
+    graphs: AtomicUsize,
+    checkpoints: AtomicUsize,
+    fail_removal: bool,
+}
+
+impl CheckpointStore for CountingStore {
+    fn save_graph(&self, execution: ExecutionId, graph: &Graph) -> Result<(), EngineError> {
+        self.graphs.fetch_add(1, Ordering::SeqCst);
+        self.store.save_graph(execution, graph)
+    }
+
+    fn save(&self, checkpoint: &Checkpoint) -> Result<(), EngineError> {
+        self.checkpoints.fetch_add(1, Ordering::SeqCst);
+        self.store.save(checkpoint)
+    }
+
+    fn remove(&self, execution: ExecutionId) -> Result<(), EngineError> {
+        if self.fail_removal {
+            return Err(EngineError::new("Disk is read-only."));
+        }
+        self.store.remove(execution)
+    }
+
+    fn executions(&self) -> Result<Vec<ExecutionId>, EngineError> {
+        self.store.executions()
+    }
+
+    fn load(&self) -> Result<Vec<Checkpoint>, EngineError> {
+        self.store.load()
+    }
+
+    fn load_graph(&self, execution: ExecutionId, schema: &Schema) -> Result<Graph, EngineError> {
+        self.store.load_graph(execution, schema)
+    }
+}
+
+fn directory(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("engine-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&path);
+    path
+}
+
+#[test]
+fn save_and_load() {
+    let library = Library::get();
+    let mut graph = common::build_graph(&library.schema).unwrap();
+    let placed_retry = RetryPolicy::new(2).fixed(Duration::from_millis(5));
+    graph.set_retry_policy("p1", placed_retry.clone()).unwrap();
+    let path = directory("checkpoint-store");
+    let store = FileStore::new(&path).unwrap();
+    let mut payload = Values::new();
+    payload.insert("iteration".into(), Value::Integer(1));
+    let mut state = Values::new();
+    state.insert("executed".into(), Value::Integer(3));
+    let states: HashMap<String, Values> = vec![("r1".into(), state)].into_iter().collect();
+    let checkpoint = Checkpoint {
+        execution: 7,
+        entries: vec![("a1".into(), "trigger".into())],
+        limits: ExecutionLimits {
+            max_steps: Some(10),
+            max_wall_time_ms: Some(5000),
+            ..ExecutionLimits::new()
+        },
+        retry_policies: vec![(
+            "p1".into(),
+            RetryPolicy::new(3)
+                .exponential(Duration::from_millis(10), Duration::from_millis(40))
+                .jitter(0.5)
+                .retry_on("io"),
+        )]
+        .into_iter()
+        .collect(),
+        priority: 2,
+        elapsed: Duration::from_millis(1500),
+        steps: 3,
+        states,
+        pending: vec![PendingInstruction {
+            node: "p1".into(),
+            command: "print".into(),
+            payload,
+            depth: 2,
+            edge: Some("r1#executed>p1#print".into()),
+        }],
+    };
+
+    store.save_graph(7, &graph).unwrap();
+    store.save(&checkpoint).unwrap();
+    let loaded = store.load().unwrap();
+    let loaded_graph = store.load_graph(7, &library.schema).unwrap();
+    store.remove(7).unwrap();
+    let empty = store.load().unwrap();
+    let files = fs::read_dir(&path).unwrap().count();
+    fs::remove_dir_all(&path).unwrap();
+
+    assert_eq!(loaded.len(), 1);
+    assert_eq!(loaded[0].execution, 7);
+    assert_eq!(loaded[0].entries, checkpoint.entries);
+    assert_eq!(loaded[0].limits, checkpoint.limits);
+    assert_eq!(loaded[0].retry_policies, checkpoint.retry_policies);
+    assert_eq!(loaded[0].priority, 2);
+    assert_eq!(loaded[0].elapsed, checkpoint.elapsed);
+    assert_eq!(loaded[0].steps, 3);
+    assert_eq!(loaded[0].states, checkpoint.states);
+    assert_eq!(loaded[0].pending, checkpoint.pending);
+    assert_eq!(loaded_graph.nodes.len(), 8);
+    assert_eq!(loaded_graph.edge_map.edges.len(), 7);
+    assert_eq!(loaded_graph.get_node("p1").retry, Some(placed_retry));
+    assert_eq!(loaded_graph.get_node("r1").retry, None);
+    assert!(empty.is_empty());
+    assert_eq!(files, 0);
+}
+
+#[test]
+fn resume() {
+    let library = Library::get();
+    let graph = common::build_graph(&library.schema).unwrap();
+    let path = directory("checkpoint-resume");
+    let store = Arc::new(FileStore::new(&path).unwrap());
+
+    // Engine without workers stops before handling anything, as if its process died.
+    let mut crashed = Engine::new(EngineConfig::load().unwrap(), library);
+    crashed.set_checkpoint_store(store.clone()).unwrap();
+    let handle = crashed.execute(graph, "a1", "trigger").unwrap();
+    let id = handle.id();
+    std::mem::forget(crashed);
+
+    let checkpoints = store.load().unwrap();
+    assert_eq!(checkpoints.len(), 1);
+    assert_eq!(checkpoints[0].pending[0].node, "a1");
+
+    let mut engine = Engine::new(EngineConfig::load().unwrap(), Library::get());
+    engine.set_checkpoint_store(store.clone()).unwrap();
+    engine.run();
+    let handles = engine.resume().unwrap();
+    assert_eq!(handles.len(), 1);
+    assert_eq!(handles[0].id(), id);
+    assert!(matches!(handles[0].join(), Outcome::Succeeded));
+    assert_eq!(handles[0].node_state("r1")["executed"], Value::Integer(3));
+    let graph = common::build_graph(&engine.library.schema).unwrap();
+    let next = engine.execute_actions(graph).unwrap();
+    assert!(next.id() > id);
+    next.join();
+
+    assert!(store.load().unwrap().is_empty());
+    fs::remove_dir_all(&path).unwrap();
+}
+
+#[test]
+fn execute_before_resume() {
+    let library = Library::get();
+    let graph = common::build_graph(&library.schema).unwrap();
+    let path = directory("checkpoint-ids");
+    let store = Arc::new(FileStore::new(&path).unwrap());
+
+    let mut crashed = Engine::new(EngineConfig::load().unwrap(), library);
+    crashed.set_checkpoint_store(store.clone()).unwrap();
+    let id = crashed
+        .execute(graph.clone(), "a1", "trigger")
+        .unwrap()
+        .id();
+    std::mem::forget(crashed);
+
+    let mut engine = Engine::new(EngineConfig::load().unwrap(), Library::get());
+    engine.set_checkpoint_store(store.clone()).unwrap();
+    let next = engine.execute(graph, "a1", "trigger").unwrap();
+    assert!(next.id() > id);
+    assert_eq!(store.executions().unwrap(), vec![id, next.id()]);
+
+    engine.run();
+    let handles = engine.resume().unwrap();
+    assert_eq!(handles.len(), 1);
+    assert_eq!(handles[0].id(), id);
+    for handle in handles.iter().chain(Some(&next)) {
+        assert!(matches!(handle.join(), Outcome::Succeeded));
+    }
+    assert!(store.executions().unwrap().is_empty());
+    fs::remove_dir_all(&path).unwrap();
+}
+
+#[test]
+fn graph_saved_once() {
+    let library = Library::get();
+    let graph = common::build_graph(&library.schema).unwrap();
+    let path = directory("checkpoint-graph");
+    let store = Arc::new(CountingStore {
+        store: FileStore::new(&path).unwrap(),
+        graphs: AtomicUsize::new(0),
+        checkpoints: AtomicUsize::new(0),
+        fail_removal: false,
+    });
+
+    let mut engine = Engine::new(EngineConfig::load().unwrap(), library);
+    engine.set_checkpoint_store(store.clone()).unwrap();
+    engine.run();
+    let handle = engine.execute(graph, "a1", "trigger").unwrap();
+    assert!(matches!(handle.join(), Outcome::Succeeded));
+
+    assert_eq!(store.graphs.load(Ordering::SeqCst), 1);
+    assert!(store.checkpoints.load(Ordering::SeqCst) > 1);
+    assert!(store.executions().unwrap().is_empty());
+    fs::remove_dir_all(&path).unwrap();
+}
+
+#[test]
+fn failed_removal() {
+    let library = Library::get();
+    let graph = common::build_graph(&library.schema).unwrap();
+    let path = directory("checkpoint-removal");
+    let store = Arc::new(CountingStore {
+        store: FileStore::new(&path).unwrap(),
+        graphs: AtomicUsize::new(0),
+        checkpoints: AtomicUsize::new(0),
+        fail_removal: true,
+    });
+
+    let mut engine = Engine::new(EngineConfig::load().unwrap(), library);
+    engine.set_checkpoint_store(store.clone()).unwrap();
+    engine.run();
+    let handle = engine.execute(graph, "a1", "trigger").unwrap();
+    match handle.join() {
+        Outcome::Failed(e) => assert_eq!(e.message, "Disk is read-only."),
+        outcome => panic!("unexpected outcome {:?}", outcome),
+    }
+    assert_eq!(store.executions().unwrap(), vec![handle.id()]);
+    fs::remove_dir_all(&path).unwrap();
+}
+
+#[test]
+fn load_invalid_retry_policy() {
+    let path = directory("checkpoint-retry-policy");
+    let store = FileStore::new(&path).unwrap();
+    store
+        .save(&Checkpoint {
+            execution: 1,
+            entries: Vec::new(),
+            limits: ExecutionLimits::new(),
+            retry_policies: vec![
+                ("p1".into(), RetryPolicy::new(2)),
+                (
+                    "p2".into(),
+                    RetryPolicy::new(3)
+                        .fixed(Duration::from_millis(10))
+                        .jitter(0.5),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            priority: 0,
+            elapsed: Duration::default(),
+            steps: 0,
+            states: HashMap::new(),
+            pending: Vec::new(),
+        })
+        .unwrap();
+    let file = path.join("execution-1.json");
+    let json = fs::read_to_string(&file)
+        .unwrap()
+        .replace("\"max_attempts\":2", "\"max_attempts\":0")
+        .replace("\"jitter\":0.5", "\"jitter\":5.0");
+    fs::write(&file, json).unwrap();
+
+    let loaded = store.load().unwrap();
+    fs::remove_dir_all(&path).unwrap();
+
+    let policies = &loaded[0].retry_policies;
+    assert_eq!(policies["p1"], RetryPolicy::new(1));
+    let jittered = RetryPolicy::new(3)
+        .fixed(Duration::from_millis(10))
+        .jitter(1.0);
+    assert_eq!(policies["p2"], jittered);
+    assert!(policies["p2"].delay(1).unwrap() <= Duration::from_millis(10));
+}
+
+#[test]
+fn resume_wall_time() {
+    let library = Library::get();
+    let graph = common::build_graph(&library.schema).unwrap();
+    let path = directory("checkpoint-wall-time");
+    let store = Arc::new(FileStore::new(&path).unwrap());
+    store.save_graph(1, &graph).unwrap();
+    store
+        .save(&Checkpoint {
+            execution: 1,
+            entries: vec![("a1".into(), "trigger".into())],
+            limits: ExecutionLimits {
+                max_wall_time_ms: Some(1000),
+                ..ExecutionLimits::new()
+            },
+            retry_policies: HashMap::new(),
+            priority: 0,
+            elapsed: Duration::from_secs(2),
+            steps: 1,
+            states: HashMap::new(),
+            pending: vec![PendingInstruction {
+                node: "a1".into(),
+                command: "trigger".into(),
+                payload: Values::new(),
+                depth: 0,
+                edge: None,
+            }],
+        })
+        .unwrap();
+
+    let mut engine = Engine::new(EngineConfig::load().unwrap(), library);
+    engine.set_checkpoint_store(store.clone()).unwrap();
+    engine.run();
+    let handles = engine.resume().unwrap();
+    match handles[0].join() {
+        Outcome::Failed(e) => assert!(e.message.contains("max wall time")),
+        outcome => panic!("unexpected outcome {:?}", outcome),
+    }
+    fs::remove_dir_all(&path).unwrap();
+}
@@ -0,0 +1,107 @@
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use graph::graph::Graph;
+use graph::value::Value;
+
+use engine::debugger::{Breakpoint, Debugger};
+use engine::execution::{ExecutionOptions, Outcome};
+use engine::library::Library;
+use engine::local::LocalExecutor;
+use engine::record::Recorder;
+
+mod common;
+
+fn debugger(library: &Arc<Library>) -> Debugger {
+    Debugger::new(LocalExecutor::new(Arc::clone(library)).unwrap())
+}
+
+#[test]
+fn command_breakpoint() {
+    let library = Arc::new(Library::get());
+    let graph = common::build_graph(&library.schema).unwrap();
+    let mut debugger = debugger(&library);
+    debugger.add_breakpoint(Breakpoint::command("p1", "print"));
+
+    let handle = debugger.execute(graph, "a1", "trigger").unwrap();
+    let print = handle.plan().find_command("p1", "print").unwrap();
+    let instruction = debugger.run().unwrap();
+    assert_eq!(instruction.command, print);
+    assert_eq!(instruction.payload["iteration"], Value::Integer(0));
+    assert_eq!(debugger.pending().len(), 3);
+    assert!(debugger.is_at_breakpoint());
+    assert_eq!(debugger.inputs().unwrap()["content"], Value::Integer(7));
+
+    let instruction = debugger.resume().unwrap();
+    assert_eq!(instruction.payload["iteration"], Value::Integer(1));
+    assert!(debugger.step());
+    assert_eq!(debugger.pending().len(), 1);
+
+    assert!(debugger.resume().is_none());
+    match handle.outcome() {
+        Some(Outcome::Succeeded) => {}
+        outcome => panic!("unexpected outcome {:?}", outcome),
+    }
+}
+
+#[test]
+fn edge_breakpoint() {
+    let library = Arc::new(Library::get());
+    let graph = common::build_graph(&library.schema).unwrap();
+    let edge = graph
+        .edge_map
+        .get_node_edges("r1")
+        .into_iter()
+        .find(|edge| edge.target.node.key == "p1")
+        .unwrap();
+    let mut debugger = debugger(&library);
+    debugger.add_breakpoint(Breakpoint::edge(&edge));
+    debugger.add_breakpoint(Breakpoint::node("a1"));
+
+    let handle = debugger.execute(graph, "a1", "trigger").unwrap();
+    assert!(debugger.is_at_breakpoint());
+    assert!(debugger.run().is_some());
+    let instruction = debugger.resume().unwrap();
+    assert_eq!(instruction.edge, Some(edge.to_string()));
+
+    assert!(debugger.remove_breakpoint(&Breakpoint::node("a1")));
+    debugger.abort();
+    assert!(debugger.current().is_none());
+    match handle.outcome() {
+        Some(Outcome::Cancelled) => {}
+        outcome => panic!("unexpected outcome {:?}", outcome),
+    }
+}
+
+#[test]
+fn recorded_inputs() {
+    let library = Arc::new(Library::get());
+    let graph = {
+        let mut gb = Graph::builder(&library.schema);
+        let a1 = gb.node("action", "a1").unwrap();
+        let p1 = gb.node("printer", "p1").unwrap();
+        let clock = gb.node("clock", "clock").unwrap();
+        gb.connect(&a1, "triggered", &p1, "print").unwrap();
+        gb.connect(&clock, "now", &p1, "content").unwrap();
+        gb.build().unwrap()
+    };
+    let mut debugger = debugger(&library);
+    debugger.add_breakpoint(Breakpoint::node("p1"));
+
+    let options = ExecutionOptions::new().recorder(Arc::new(Recorder::record()));
+    let handle = debugger
+        .executor()
+        .execute_with(graph, "a1", "trigger", options)
+        .unwrap();
+    assert!(debugger.run().is_some());
+    let content = debugger.inputs().unwrap()["content"].clone();
+    thread::sleep(Duration::from_millis(5));
+    assert_eq!(debugger.inputs().unwrap()["content"], content);
+
+    assert!(debugger.resume().is_none());
+    assert!(matches!(handle.outcome(), Some(Outcome::Succeeded)));
+    let recording = handle.recording().unwrap();
+    assert_eq!(recording.values.len(), 1);
+    assert_eq!(recording.values[0].outputs["now"], content);
+}
@@ -0,0 +1,57 @@
+use std::sync::Arc;
+
+use engine::execution::Execution;
+use engine::library::{Library, Values};
+use engine::message::Instruction;
+use engine::plan::ExecutionPlan;
+use engine::processor::action_processor::ActionProcessor;
+use engine::processor::repeat_processor::RepeatProcessor;
+use engine::processor::{Invocation, Processor};
+
+mod common;
+
+#[test]
+fn route_action() {
+    let library = Arc::new(Library::get());
+    let graph = common::build_graph(&library.schema).unwrap();
+    let plan = Arc::new(ExecutionPlan::compile(&graph, &library).unwrap());
+    let execution = Arc::new(Execution::new(0, Arc::clone(&plan)));
+    let processor = ActionProcessor::new(&library).unwrap();
+
+    let trigger = plan.find_command("a1", "trigger").unwrap();
+    let instruction = Instruction::new(Arc::clone(&execution), trigger, Values::new());
+    let handler = processor.router().resolve(&instruction).unwrap();
+    let mut invocation = Invocation::new(&instruction);
+    handler(&mut invocation).unwrap();
+
+    let events = invocation.take_events();
+    assert_eq!(events.len(), 1);
+    let targets = &plan.events[events[0].0].targets;
+    assert_eq!(targets.len(), 1);
+    assert_eq!(
+        targets[0].command,
+        plan.find_command("r1", "start").unwrap()
+    );
+
+    let print = plan.find_command("p1", "print").unwrap();
+    let instruction = Instruction::new(execution, print, Values::new());
+    assert!(processor.router().resolve(&instruction).is_none());
+}
+
+#[test]
+fn observe_cancellation() {
+    let library = Arc::new(Library::get());
+    let graph = common::build_graph(&library.schema).unwrap();
+    let plan = Arc::new(ExecutionPlan::compile(&graph, &library).unwrap());
+    let execution = Arc::new(Execution::new(0, Arc::clone(&plan)));
+    let processor = RepeatProcessor::new(&library).unwrap();
+
+    let start = plan.find_command("r1", "start").unwrap();
+    let instruction = Instruction::new(Arc::clone(&execution), start, Values::new());
+    execution.cancel();
+    let handler = processor.router().resolve(&instruction).unwrap();
+    let mut invocation = Invocation::new(&instruction);
+    assert!(invocation.is_cancelled());
+    handler(&mut invocation).unwrap();
+    assert!(invocation.events().is_empty());
+}
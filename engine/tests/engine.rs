@@ -1,44 +1,189 @@
-use engine::{Engine, EngineConfig};
-use engine::library::Library;
-use graph::error::GraphError;
-use graph::graph::Graph;
-use graph::schema::Schema;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
 use graph::value::Value;
 
+use engine::error::EngineError;
+use engine::execution::{Execution, ExecutionStatus, Outcome};
+use engine::library::Library;
+use engine::plan::ExecutionPlan;
+use engine::{Engine, EngineConfig, ShutdownMode};
+
+mod common;
+
 #[test]
 fn basic() {
     let library = Library::get();
-    let build_graph = |schema: &Schema| -> Result<Graph, GraphError> {
-        let mut gb = Graph::builder(schema);
-        let a1 = gb.node("action", "a1")?;
-        let r1 = gb.node("repeat", "r1")?;
-        let p1 = gb.node("printer", "p1")?;
-        let minus = gb.node("minus", "minus")?;
-        let plus = gb.node("plus", "plus")?;
-        let six = gb.node("integer", "six")?;
-        let four = gb.node("integer", "four")?;
-        let three = gb.node("integer", "three")?;
-
-        gb.assign(&r1, "times", Value::Integer(3))?;
-        gb.assign(&six, "value", Value::Integer(6))?;
-        gb.assign(&four, "value", Value::Integer(4))?;
-        gb.assign(&three, "value", Value::Integer(3))?;
-
-        gb.connect(&a1, "triggered", &r1, "start")?;
-        gb.connect(&r1, "executed", &p1, "print")?;
-        gb.connect(&six, "return-value", &plus, "a")?;
-        gb.connect(&four, "return-value", &plus, "b")?;
-        gb.connect(&plus, "c", &minus, "a")?;
-        gb.connect(&three, "return-value", &minus, "b")?;
-        gb.connect(&minus, "c", &p1, "content")?;
-
-        gb.build()
-    };
-
-    let graph = build_graph(&library.schema).unwrap();
+    let graph = common::build_graph(&library.schema).unwrap();
     let engine_config = EngineConfig::load().unwrap();
     let mut engine = Engine::new(engine_config, library);
 
     engine.run();
-    engine.execute(graph);
+    let handle = engine.execute(graph, "a1", "trigger").unwrap();
+    assert_eq!(handle.entries().len(), 1);
+    match handle.join_timeout(Duration::from_secs(5)) {
+        Some(Outcome::Succeeded) => {}
+        outcome => panic!("unexpected outcome {:?}", outcome),
+    }
+    match handle.status() {
+        ExecutionStatus::Finished(Outcome::Succeeded) => {}
+        status => panic!("unexpected status {:?}", status),
+    }
+}
+
+#[test]
+fn execution_ids() {
+    let library = Library::get();
+    let graph = common::build_graph(&library.schema).unwrap();
+    let mut engine = Engine::new(EngineConfig::load().unwrap(), library);
+
+    engine.run();
+    let first = engine.execute(graph.clone(), "a1", "trigger").unwrap();
+    let second = engine.execute(graph, "a1", "trigger").unwrap();
+    assert_ne!(first.id(), second.id());
+    first.join();
+    second.join();
+}
+
+#[test]
+fn isolated_executions() {
+    let library = Library::get();
+    let graph = common::build_graph(&library.schema).unwrap();
+    let mut engine = Engine::new(EngineConfig::load().unwrap(), library);
+
+    let first = engine.execute(graph.clone(), "a1", "trigger").unwrap();
+    let second = engine.execute(graph, "a1", "trigger").unwrap();
+    let active: Vec<u64> = engine.executions().iter().map(|e| e.id()).collect();
+    assert_eq!(active, vec![first.id(), second.id()]);
+    assert_eq!(engine.execution(second.id()).unwrap().in_flight(), 1);
+    assert!(engine.execution(second.id() + 1).is_none());
+
+    engine.run();
+    first.join();
+    second.join();
+    assert_eq!(first.node_state("r1")["executed"], Value::Integer(3));
+    assert_eq!(second.node_state("r1")["executed"], Value::Integer(3));
+    assert_eq!(first.steps(), 5);
+    assert!(engine.executions().is_empty());
+    assert!(engine.execution(first.id()).is_none());
+}
+
+#[test]
+fn failure() {
+    let library = Library::get();
+    let mut graph = common::build_graph(&library.schema).unwrap();
+    graph
+        .assign("six", "value", Value::Integer(i64::MAX))
+        .unwrap();
+    let mut engine = Engine::new(EngineConfig::load().unwrap(), library);
+
+    engine.run();
+    let handle = engine.execute(graph, "a1", "trigger").unwrap();
+    match handle.join() {
+        Outcome::Failed(_) => {}
+        outcome => panic!("unexpected outcome {:?}", outcome),
+    }
+}
+
+#[test]
+fn entry_points() {
+    let library = Library::get();
+    let graph = common::build_graph(&library.schema).unwrap();
+    let mut engine = Engine::new(EngineConfig::load().unwrap(), library);
+
+    engine.run();
+    assert!(engine.execute(graph.clone(), "p1", "missing").is_err());
+    assert!(engine.execute(graph.clone(), "missing", "trigger").is_err());
+    let handle = engine.execute_actions(graph).unwrap();
+    let trigger = handle.plan().find_command("a1", "trigger").unwrap();
+    assert_eq!(handle.entries(), &[trigger]);
+}
+
+#[test]
+fn cancel() {
+    let library = Library::get();
+    let graph = common::build_graph(&library.schema).unwrap();
+    let mut engine = Engine::new(EngineConfig::load().unwrap(), library);
+
+    let handle = engine.execute(graph, "a1", "trigger").unwrap();
+    handle.cancel();
+    engine.run();
+    match handle.join() {
+        Outcome::Cancelled => {}
+        outcome => panic!("unexpected outcome {:?}", outcome),
+    }
+}
+
+#[test]
+fn shutdown() {
+    let library = Library::get();
+    let graph = common::build_graph(&library.schema).unwrap();
+    let mut engine = Engine::new(EngineConfig::load().unwrap(), library);
+
+    engine.run();
+    let handle = engine.execute(graph.clone(), "a1", "trigger").unwrap();
+    engine.shutdown(ShutdownMode::Drain).unwrap();
+    match handle.outcome() {
+        Some(Outcome::Succeeded) => {}
+        outcome => panic!("unexpected outcome {:?}", outcome),
+    }
+    assert!(engine.execute(graph.clone(), "a1", "trigger").is_err());
+
+    engine.run();
+    let handle = engine.execute(graph, "a1", "trigger").unwrap();
+    drop(engine);
+    assert!(handle.outcome().is_some());
+}
+
+#[test]
+fn abort() {
+    let library = Library::get();
+    let graph = common::build_graph(&library.schema).unwrap();
+    let mut engine = Engine::new(EngineConfig::load().unwrap(), library);
+
+    let handle = engine.execute(graph, "a1", "trigger").unwrap();
+    engine.shutdown(ShutdownMode::Abort).unwrap();
+    match handle.outcome() {
+        Some(Outcome::Cancelled) => {}
+        outcome => panic!("unexpected outcome {:?}", outcome),
+    }
+}
+
+#[test]
+fn update_node_state() {
+    let library = Library::get();
+    let graph = common::build_graph(&library.schema).unwrap();
+    let plan = Arc::new(ExecutionPlan::compile(&graph, &library).unwrap());
+    let execution = Arc::new(Execution::new(1, plan));
+
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let execution = Arc::clone(&execution);
+            thread::spawn(move || {
+                for _ in 0..100 {
+                    execution.update_node_state("r1", |state| {
+                        let count = match state.get("count") {
+                            Some(Value::Integer(count)) => *count,
+                            _ => 0,
+                        };
+                        state.insert("count".into(), Value::Integer(count + 1));
+                    });
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(execution.node_state("r1")["count"], Value::Integer(400));
+
+    let seen = execution.update_node_state("r1", |state| {
+        state.insert("done".into(), Value::Boolean(true));
+        execution.fail(EngineError::new("Stopped while updating."));
+        execution.node_state("r1").len()
+    });
+    assert_eq!(seen, 1);
+    assert_eq!(execution.node_state("r1")["done"], Value::Boolean(true));
+    assert!(execution.is_finished());
 }
@@ -0,0 +1,89 @@
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use graph::error::GraphError;
+use graph::graph::Graph;
+use graph::schema::Schema;
+use graph::value::Value;
+
+use engine::error::{EngineError, ErrorOrigin};
+use engine::execution::Outcome;
+use engine::library::Library;
+use engine::local::LocalExecutor;
+use engine::processor::action_processor::ActionProcessor;
+use engine::processor::printer_processor::PrinterProcessor;
+use engine::processor::Processor;
+
+/// Builds a graph whose printer `p1` fails on overflow, optionally handled by printer `p2`.
+fn build_graph(schema: &Schema, handled: bool) -> Result<Graph, GraphError> {
+    let mut gb = Graph::builder(schema);
+    let a1 = gb.node("action", "a1")?;
+    let p1 = gb.node("printer", "p1")?;
+    let p2 = gb.node("printer", "p2")?;
+    let plus = gb.node("plus", "plus")?;
+    let max = gb.node("integer", "max")?;
+    let four = gb.node("integer", "four")?;
+
+    gb.assign(&max, "value", Value::Integer(i64::MAX))?;
+    gb.assign(&four, "value", Value::Integer(4))?;
+
+    gb.connect(&a1, "triggered", &p1, "print")?;
+    if handled {
+        gb.connect(&p1, "failed", &p2, "print")?;
+    }
+    gb.connect(&max, "return-value", &plus, "a")?;
+    gb.connect(&four, "return-value", &plus, "b")?;
+    gb.connect(&plus, "c", &p1, "content")?;
+    gb.connect(&four, "return-value", &p2, "content")?;
+
+    gb.build()
+}
+
+fn run(handled: bool) -> (Outcome, String) {
+    let library = Arc::new(Library::get());
+    let graph = build_graph(&library.schema, handled).unwrap();
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let writer: Arc<Mutex<dyn Write + Send>> = output.clone();
+    let processors: Vec<Box<dyn Processor>> = vec![
+        Box::new(ActionProcessor::new(&library).unwrap()),
+        Box::new(PrinterProcessor::with_writer(&library, writer).unwrap()),
+    ];
+    let mut executor = LocalExecutor::with_processors(Arc::clone(&library), processors);
+
+    let handle = executor.execute(graph, "a1", "trigger").unwrap();
+    executor.run_until_idle();
+    let output = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+    (handle.outcome().unwrap(), output)
+}
+
+#[test]
+fn handled() {
+    let (outcome, output) = run(true);
+    assert!(matches!(outcome, Outcome::Succeeded));
+    assert_eq!(output, "4\n");
+}
+
+#[test]
+fn unhandled() {
+    let (outcome, output) = run(false);
+    match outcome {
+        Outcome::Failed(e) => assert_eq!(
+            e.origin,
+            Some(ErrorOrigin {
+                node: "p1".into(),
+                command: "print".into()
+            })
+        ),
+        outcome => panic!("unexpected outcome {:?}", outcome),
+    }
+    assert_eq!(output, "");
+}
+
+#[test]
+fn payload() {
+    let error = EngineError::with_code("io", "Failed to print.").raised_by("p1", "print");
+    let payload = error.payload();
+    assert_eq!(payload["message"], Value::String("Failed to print.".into()));
+    assert_eq!(payload["code"], Value::String("io".into()));
+    assert_eq!(payload["command"], Value::String("print".into()));
+}
@@ -0,0 +1,137 @@
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use graph::graph::Graph;
+use graph::value::Value;
+
+use engine::execution::Outcome;
+use engine::library::basic::repeat;
+use engine::library::Library;
+use engine::local::LocalExecutor;
+use engine::metrics::PrometheusRegistry;
+use engine::processor::action_processor::ActionProcessor;
+use engine::processor::printer_processor::PrinterProcessor;
+use engine::processor::repeat_processor::RepeatProcessor;
+use engine::processor::Processor;
+
+mod common;
+
+fn executor(library: &Arc<Library>, output: &Arc<Mutex<Vec<u8>>>) -> LocalExecutor {
+    let writer: Arc<Mutex<dyn Write + Send>> = output.clone();
+    let processors: Vec<Box<dyn Processor>> = vec![
+        Box::new(ActionProcessor::new(library).unwrap()),
+        Box::new(PrinterProcessor::with_writer(library, writer).unwrap()),
+        Box::new(RepeatProcessor::new(library).unwrap()),
+    ];
+    LocalExecutor::with_processors(Arc::clone(library), processors)
+}
+
+#[test]
+fn print_arithmetic() {
+    let library = Arc::new(Library::get());
+    let graph = common::build_graph(&library.schema).unwrap();
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut executor = executor(&library, &output);
+
+    let handle = executor.execute_actions(graph).unwrap();
+    executor.run_until_idle();
+    assert!(matches!(handle.outcome(), Some(Outcome::Succeeded)));
+    assert_eq!(handle.node_state("r1")["executed"], Value::Integer(3));
+    let output = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+    assert_eq!(output, "7\n7\n7\n");
+}
+
+#[test]
+fn repeat_in_batches() {
+    let library = Arc::new(Library::get());
+    let times = repeat::BATCH_SIZE * 4 + 1;
+    let graph = {
+        let mut gb = Graph::builder(&library.schema);
+        let a1 = gb.node("action", "a1").unwrap();
+        let r1 = gb.node("repeat", "r1").unwrap();
+        let p1 = gb.node("printer", "p1").unwrap();
+        let one = gb.node("integer", "one").unwrap();
+        gb.assign(&r1, "times", Value::Integer(times)).unwrap();
+        gb.assign(&one, "value", Value::Integer(1)).unwrap();
+        gb.connect(&a1, "triggered", &r1, "start").unwrap();
+        gb.connect(&r1, "executed", &p1, "print").unwrap();
+        gb.connect(&one, "return-value", &p1, "content").unwrap();
+        gb.build().unwrap()
+    };
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let registry = Arc::new(PrometheusRegistry::new());
+    let mut executor = executor(&library, &output);
+    executor.set_metrics_sink(registry.clone());
+
+    let handle = executor.execute_actions(graph).unwrap();
+    let mut most_pending = 0;
+    while executor.step() {
+        most_pending = most_pending.max(executor.pending().len());
+    }
+    assert!(matches!(handle.outcome(), Some(Outcome::Succeeded)));
+    assert!(most_pending <= repeat::BATCH_SIZE as usize + 1);
+    let state = handle.node_state("r1");
+    assert_eq!(state["executed"], Value::Integer(times));
+    assert_eq!(state["loops"], Value::Integer(1));
+    assert_eq!(state.len(), 2);
+    let output = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+    assert_eq!(output, "1\n".repeat(times as usize));
+    let text = registry.render();
+    assert!(text
+        .lines()
+        .any(|line| line
+            == "engine_dispatched_total{node=\"repeat\",key=\"r1\",command=\"start\"} 5"));
+}
+
+#[test]
+fn repeat_loops_separately() {
+    let library = Arc::new(Library::get());
+    let times = repeat::BATCH_SIZE + 1;
+    let graph = {
+        let mut gb = Graph::builder(&library.schema);
+        let a1 = gb.node("action", "a1").unwrap();
+        let a2 = gb.node("action", "a2").unwrap();
+        let r1 = gb.node("repeat", "r1").unwrap();
+        let p1 = gb.node("printer", "p1").unwrap();
+        let one = gb.node("integer", "one").unwrap();
+        gb.assign(&r1, "times", Value::Integer(times)).unwrap();
+        gb.assign(&one, "value", Value::Integer(1)).unwrap();
+        gb.connect(&a1, "triggered", &r1, "start").unwrap();
+        gb.connect(&a2, "triggered", &r1, "start").unwrap();
+        gb.connect(&r1, "executed", &p1, "print").unwrap();
+        gb.connect(&one, "return-value", &p1, "content").unwrap();
+        gb.build().unwrap()
+    };
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut executor = executor(&library, &output);
+
+    let handle = executor.execute_actions(graph).unwrap();
+    executor.run_until_idle();
+    assert!(matches!(handle.outcome(), Some(Outcome::Succeeded)));
+    let state = handle.node_state("r1");
+    assert_eq!(state["executed"], Value::Integer(times * 2));
+    assert_eq!(state["loops"], Value::Integer(2));
+    assert_eq!(state.len(), 2);
+    let output = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+    assert_eq!(output, "1\n".repeat(times as usize * 2));
+}
+
+#[test]
+fn command_keys() {
+    let library = Library::get();
+    let mut keys: Vec<usize> = vec![
+        ("action", "trigger"),
+        ("printer", "print"),
+        ("repeat", "start"),
+    ]
+    .into_iter()
+    .map(|(node, command)| library.get_command(node, command).unwrap().key)
+    .collect();
+    keys.sort_unstable();
+    keys.dedup();
+    assert_eq!(keys.len(), 3);
+    assert_eq!(
+        library.get_command("printer", "print").unwrap().key,
+        Library::get().get_command("printer", "print").unwrap().key
+    );
+}
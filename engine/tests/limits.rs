@@ -0,0 +1,89 @@
+use std::sync::Arc;
+
+use engine::execution::{ExecutionLimits, ExecutionOptions, Outcome};
+use engine::library::Library;
+use engine::local::LocalExecutor;
+use engine::{Engine, EngineConfig};
+
+mod common;
+
+fn run_local(limits: ExecutionLimits) -> Outcome {
+    let library = Arc::new(Library::get());
+    let graph = common::build_graph(&library.schema).unwrap();
+    let mut executor = LocalExecutor::new(Arc::clone(&library)).unwrap();
+    let options = ExecutionOptions::new().limits(limits);
+    let handle = executor
+        .execute_with(graph, "a1", "trigger", options)
+        .unwrap();
+    executor.run_until_idle();
+    handle.outcome().unwrap()
+}
+
+#[test]
+fn max_steps() {
+    let limits = ExecutionLimits {
+        max_steps: Some(3),
+        ..ExecutionLimits::new()
+    };
+    match run_local(limits) {
+        Outcome::Failed(e) => assert!(e.message.contains("exceeded max steps (3)")),
+        outcome => panic!("unexpected outcome {:?}", outcome),
+    }
+
+    let limits = ExecutionLimits {
+        max_steps: Some(5),
+        ..ExecutionLimits::new()
+    };
+    assert!(matches!(run_local(limits), Outcome::Succeeded));
+}
+
+#[test]
+fn max_depth() {
+    let limits = ExecutionLimits {
+        max_depth: Some(1),
+        ..ExecutionLimits::new()
+    };
+    match run_local(limits) {
+        Outcome::Failed(e) => assert!(e.message.contains("exceeded max depth (1)")),
+        outcome => panic!("unexpected outcome {:?}", outcome),
+    }
+}
+
+#[test]
+fn max_wall_time() {
+    let library = Library::get();
+    let graph = common::build_graph(&library.schema).unwrap();
+    let mut config = EngineConfig::load().unwrap();
+    config.limits.max_wall_time_ms = Some(0);
+    let mut engine = Engine::new(config, library);
+
+    engine.run();
+    let handle = engine.execute(graph, "a1", "trigger").unwrap();
+    match handle.join() {
+        Outcome::Failed(e) => assert!(e.message.contains("exceeded max wall time (0 ms)")),
+        outcome => panic!("unexpected outcome {:?}", outcome),
+    }
+}
+
+#[test]
+fn override_per_call() {
+    let library = Library::get();
+    let graph = common::build_graph(&library.schema).unwrap();
+    let mut config = EngineConfig::load().unwrap();
+    config.limits.max_steps = Some(1);
+    let mut engine = Engine::new(config, library);
+
+    engine.run();
+    let limits = ExecutionLimits {
+        max_steps: Some(10),
+        ..ExecutionLimits::new()
+    };
+    let options = ExecutionOptions::new().limits(limits);
+    let handle = engine
+        .execute_with(graph.clone(), "a1", "trigger", options)
+        .unwrap();
+    assert!(matches!(handle.join(), Outcome::Succeeded));
+
+    let handle = engine.execute(graph, "a1", "trigger").unwrap();
+    assert!(matches!(handle.join(), Outcome::Failed(_)));
+}
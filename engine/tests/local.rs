@@ -0,0 +1,76 @@
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use graph::value::Value;
+
+use engine::execution::Outcome;
+use engine::library::Library;
+use engine::local::LocalExecutor;
+use engine::processor::action_processor::ActionProcessor;
+use engine::processor::printer_processor::PrinterProcessor;
+use engine::processor::repeat_processor::RepeatProcessor;
+use engine::processor::Processor;
+
+mod common;
+
+fn executor(library: &Arc<Library>, output: &Arc<Mutex<Vec<u8>>>) -> LocalExecutor {
+    let writer: Arc<Mutex<dyn Write + Send>> = output.clone();
+    let processors: Vec<Box<dyn Processor>> = vec![
+        Box::new(ActionProcessor::new(library).unwrap()),
+        Box::new(PrinterProcessor::with_writer(library, writer).unwrap()),
+        Box::new(RepeatProcessor::new(library).unwrap()),
+    ];
+    LocalExecutor::with_processors(Arc::clone(library), processors)
+}
+
+#[test]
+fn run_until_idle() {
+    let library = Arc::new(Library::get());
+    let graph = common::build_graph(&library.schema).unwrap();
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut executor = executor(&library, &output);
+
+    let handle = executor.execute_actions(graph).unwrap();
+    assert!(handle.outcome().is_none());
+    assert_eq!(executor.run_until_idle(), 5);
+    assert!(executor.is_idle());
+    match handle.outcome() {
+        Some(Outcome::Succeeded) => {}
+        outcome => panic!("unexpected outcome {:?}", outcome),
+    }
+    let output = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+    assert_eq!(output, "7\n7\n7\n");
+}
+
+#[test]
+fn step() {
+    let library = Arc::new(Library::get());
+    let graph = common::build_graph(&library.schema).unwrap();
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut executor = executor(&library, &output);
+
+    let handle = executor.execute(graph, "a1", "trigger").unwrap();
+    let plan = Arc::clone(handle.plan());
+    assert!(executor.step());
+    let start = plan.find_command("r1", "start").unwrap();
+    let pending: Vec<_> = executor.pending().iter().map(|i| i.command).collect();
+    assert_eq!(pending, vec![start]);
+
+    assert!(executor.step());
+    let iterations: Vec<_> = executor
+        .pending()
+        .iter()
+        .map(|i| i.payload["iteration"].clone())
+        .collect();
+    assert_eq!(
+        iterations,
+        vec![Value::Integer(0), Value::Integer(1), Value::Integer(2)]
+    );
+
+    assert!(executor.step());
+    assert_eq!(executor.pending().len(), 2);
+    assert_eq!(
+        String::from_utf8(output.lock().unwrap().clone()).unwrap(),
+        "7\n"
+    );
+}
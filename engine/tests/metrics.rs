@@ -0,0 +1,58 @@
+use std::sync::Arc;
+
+use engine::library::Library;
+use engine::local::LocalExecutor;
+use engine::metrics::PrometheusRegistry;
+use engine::{Engine, EngineConfig};
+
+mod common;
+
+#[test]
+fn prometheus() {
+    let library = Arc::new(Library::get());
+    let graph = common::build_graph(&library.schema).unwrap();
+    let registry = Arc::new(PrometheusRegistry::new());
+    let mut executor = LocalExecutor::new(Arc::clone(&library)).unwrap();
+    executor.set_metrics_sink(registry.clone());
+
+    executor.execute(graph, "a1", "trigger").unwrap();
+    executor.run_until_idle();
+
+    let text = registry.render();
+    let lines: Vec<&str> = text.lines().collect();
+    assert!(lines.contains(&"# TYPE engine_dispatched_total counter"));
+    assert!(lines
+        .contains(&"engine_dispatched_total{node=\"action\",key=\"a1\",command=\"trigger\"} 1"));
+    assert!(
+        lines.contains(&"engine_dispatched_total{node=\"printer\",key=\"p1\",command=\"print\"} 3")
+    );
+    assert!(lines.contains(&"engine_errors_total{node=\"printer\",key=\"p1\",command=\"print\"} 0"));
+    assert!(lines.contains(
+        &"engine_handler_duration_seconds_count{node=\"printer\",key=\"p1\",command=\"print\"} 3"
+    ));
+    assert!(lines.contains(
+        &"engine_queue_wait_seconds_bucket{node=\"repeat\",key=\"r1\",command=\"start\",le=\"+Inf\"} 1"
+    ));
+    assert!(lines.contains(&"engine_queue_depth 0"));
+}
+
+#[test]
+fn worker_utilization() {
+    let library = Library::get();
+    let graph = common::build_graph(&library.schema).unwrap();
+    let registry = Arc::new(PrometheusRegistry::new());
+    let mut engine = Engine::new(EngineConfig::load().unwrap(), library);
+    engine.set_metrics_sink(registry.clone());
+
+    engine.run();
+    engine.execute(graph, "a1", "trigger").unwrap().join();
+
+    let text = registry.render();
+    let ratio: f64 = text
+        .lines()
+        .find(|line| line.starts_with("engine_worker_utilization{"))
+        .and_then(|line| line.rsplit(' ').next())
+        .and_then(|ratio| ratio.parse().ok())
+        .unwrap();
+    assert!((0.0..=1.0).contains(&ratio));
+}
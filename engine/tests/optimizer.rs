@@ -0,0 +1,78 @@
+use engine::library::Library;
+use engine::optimizer::fold_constants;
+use graph::graph::Graph;
+use graph::value::Value;
+
+mod common;
+
+#[test]
+fn fold_arithmetic() {
+    let library = Library::get();
+    let mut graph = common::build_graph(&library.schema).unwrap();
+
+    let report = fold_constants(&mut graph, &library).unwrap();
+
+    let mut folded: Vec<&str> = report.nodes.iter().map(|node| node.key.as_str()).collect();
+    folded.sort();
+    assert_eq!(folded, vec!["four", "minus", "plus", "six", "three"]);
+    assert_eq!(report.inputs.len(), 1);
+    assert_eq!(report.inputs[0].key, "p1");
+    assert_eq!(report.inputs[0].value, Value::Integer(7));
+
+    assert_eq!(graph.nodes.len(), 3);
+    assert_eq!(graph.edge_map.edges.len(), 2);
+    assert_eq!(
+        graph.get_node("p1").values.get("content").unwrap().value,
+        Value::Integer(7)
+    );
+}
+
+#[test]
+fn fold_overflow() {
+    let library = Library::get();
+    let mut graph = {
+        let mut gb = Graph::builder(&library.schema);
+        let max = gb.node("integer", "max").unwrap();
+        let one = gb.node("integer", "one").unwrap();
+        let plus = gb.node("plus", "plus").unwrap();
+        gb.assign(&max, "value", Value::Integer(i64::MAX)).unwrap();
+        gb.assign(&one, "value", Value::Integer(1)).unwrap();
+        gb.connect(&max, "return-value", &plus, "a").unwrap();
+        gb.connect(&one, "return-value", &plus, "b").unwrap();
+        gb.build().unwrap()
+    };
+
+    assert!(fold_constants(&mut graph, &library).is_err());
+    assert_eq!(graph.nodes.len(), 3);
+}
+
+#[test]
+fn fold_with_ports() {
+    let library = Library::get();
+    let mut graph = {
+        let mut gb = Graph::builder(&library.schema);
+        let six = gb.node("integer", "six").unwrap();
+        let four = gb.node("integer", "four").unwrap();
+        let plus = gb.node("plus", "plus").unwrap();
+        let minus = gb.node("minus", "minus").unwrap();
+        gb.assign(&six, "value", Value::Integer(6)).unwrap();
+        gb.assign(&four, "value", Value::Integer(4)).unwrap();
+        gb.connect(&six, "return-value", &plus, "a").unwrap();
+        gb.connect(&four, "return-value", &plus, "b").unwrap();
+        gb.connect(&plus, "c", &minus, "a").unwrap();
+        gb.parameter("x", &minus, "b").unwrap();
+        gb.result("sum", &plus, "c").unwrap();
+        gb.build().unwrap()
+    };
+
+    let report = fold_constants(&mut graph, &library).unwrap();
+
+    assert_eq!(report.nodes.len(), 2);
+    assert_eq!(graph.nodes.len(), 2);
+    assert_eq!(
+        graph.get_node("plus").values.get("a").unwrap().value,
+        Value::Integer(6)
+    );
+    assert_eq!(graph.edge_map.edges.len(), 1);
+    assert_eq!(graph.results.len(), 1);
+}
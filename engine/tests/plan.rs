@@ -0,0 +1,100 @@
+use engine::error::EngineError;
+use engine::library::{Library, Values};
+use engine::plan::{ExecutionPlan, SlotSource};
+use graph::graph::edge::{Edge, Hook};
+use graph::graph::Graph;
+use graph::schema::node::Node;
+use graph::schema::Schema;
+use graph::value::Value;
+
+mod common;
+
+#[test]
+fn compile() {
+    let library = Library::get();
+    let graph = common::build_graph(&library.schema).unwrap();
+
+    let plan = ExecutionPlan::compile(&graph, &library).unwrap();
+
+    assert_eq!(plan.nodes.len(), 8);
+    assert_eq!(plan.commands.len(), 3);
+    assert_eq!(plan.events.len(), 3);
+
+    let trigger = plan.find_command("a1", "trigger").unwrap();
+    let a1 = plan.get_node_index("a1").unwrap();
+    let triggered = plan.get_event_index(a1, "triggered").unwrap();
+    let start = plan.find_command("r1", "start").unwrap();
+    assert_eq!(plan.commands[trigger].node, a1);
+    assert_eq!(plan.events[triggered].targets.len(), 1);
+    assert_eq!(plan.events[triggered].targets[0].command, start);
+
+    let print = plan.find_command("p1", "print").unwrap();
+    let p1 = plan.get_node_index("p1").unwrap();
+    let minus = plan.get_node_index("minus").unwrap();
+    let content = plan.get_slot_index(p1, "content").unwrap();
+    let c = plan.get_slot_index(minus, "c").unwrap();
+    assert_eq!(plan.commands[print].dependencies, vec![content]);
+    match plan.slots[content].source {
+        SlotSource::Link(slot) => assert_eq!(slot, c),
+        ref source => panic!("unexpected source {:?}", source),
+    }
+    assert!(plan.nodes[minus].function.is_some());
+    assert!(plan.find_command("p1", "missing").is_err());
+}
+
+#[test]
+fn compile_unsupported_node() {
+    let library = Library::get();
+    let schema = Schema::builder()
+        .node(Node::builder("unknown").command("run").build())
+        .build();
+    let graph = {
+        let mut gb = Graph::builder(&schema);
+        gb.node("unknown", "u1").unwrap();
+        gb.build().unwrap()
+    };
+
+    assert!(ExecutionPlan::compile(&graph, &library).is_err());
+}
+
+#[test]
+fn compile_fan_in() {
+    let library = Library::get();
+    let mut graph = common::build_graph(&library.schema).unwrap();
+    let three = graph.get_node("three").clone();
+    let plus = graph.get_node("plus").clone();
+    let output = Hook::new(three.clone(), three.get_property("return-value").clone());
+    let input = Hook::new(plus.clone(), plus.get_property("a").clone());
+    graph.edge_map.insert(&Edge::new(output, input));
+
+    let error = ExecutionPlan::compile(&graph, &library).unwrap_err();
+    assert_eq!(error.message, "Input 'plus#a' is connected more than once.");
+}
+
+#[test]
+fn compile_guard() {
+    let library = Library::get();
+    let graph = {
+        let mut gb = Graph::builder(&library.schema);
+        let r1 = gb.node("repeat", "r1").unwrap();
+        let p1 = gb.node("printer", "p1").unwrap();
+        gb.connect_with_guard(&r1, "executed", &p1, "print", "iteration > 0")
+            .unwrap();
+        gb.build().unwrap()
+    };
+
+    let plan = ExecutionPlan::compile(&graph, &library).unwrap();
+    let r1 = plan.get_node_index("r1").unwrap();
+    let executed = plan.get_event_index(r1, "executed").unwrap();
+    let target = &plan.events[executed].targets[0];
+    let payload = |iteration: i64| {
+        let mut payload = Values::new();
+        payload.insert(String::from("iteration"), Value::Integer(iteration));
+        payload
+    };
+    let resolve = |_| -> Result<Value, EngineError> { unreachable!() };
+
+    assert!(!target.allows(&payload(0), resolve).unwrap());
+    assert!(target.allows(&payload(1), resolve).unwrap());
+    assert!(target.allows(&Values::new(), resolve).is_err());
+}
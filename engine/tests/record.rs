@@ -0,0 +1,118 @@
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use graph::error::GraphError;
+use graph::graph::Graph;
+use graph::schema::Schema;
+use graph::value::Value;
+
+use engine::execution::{ExecutionOptions, Outcome};
+use engine::library::Library;
+use engine::local::LocalExecutor;
+use engine::optimizer::fold_constants;
+use engine::processor::action_processor::ActionProcessor;
+use engine::processor::printer_processor::PrinterProcessor;
+use engine::processor::repeat_processor::RepeatProcessor;
+use engine::processor::Processor;
+use engine::record::{Recorder, Recording};
+use engine::{Engine, EngineConfig};
+
+/// Builds a graph that prints current time `times` times when `a1` is triggered.
+fn build_clock_graph(schema: &Schema, times: i64) -> Result<Graph, GraphError> {
+    let mut gb = Graph::builder(schema);
+    let a1 = gb.node("action", "a1")?;
+    let r1 = gb.node("repeat", "r1")?;
+    let p1 = gb.node("printer", "p1")?;
+    let clock = gb.node("clock", "clock")?;
+    gb.assign(&r1, "times", Value::Integer(times))?;
+    gb.connect(&a1, "triggered", &r1, "start")?;
+    gb.connect(&r1, "executed", &p1, "print")?;
+    gb.connect(&clock, "now", &p1, "content")?;
+    gb.build()
+}
+
+fn executor(library: &Arc<Library>, output: &Arc<Mutex<Vec<u8>>>) -> LocalExecutor {
+    let writer: Arc<Mutex<dyn Write + Send>> = output.clone();
+    let processors: Vec<Box<dyn Processor>> = vec![
+        Box::new(ActionProcessor::new(library).unwrap()),
+        Box::new(PrinterProcessor::with_writer(library, writer).unwrap()),
+        Box::new(RepeatProcessor::new(library).unwrap()),
+    ];
+    LocalExecutor::with_processors(Arc::clone(library), processors)
+}
+
+#[test]
+fn record_and_replay() {
+    let library = Library::get();
+    let graph = build_clock_graph(&library.schema, 3).unwrap();
+    let mut engine = Engine::new(EngineConfig::load().unwrap(), library);
+    engine.run();
+
+    let recorder = Arc::new(Recorder::record());
+    let options = ExecutionOptions::new().recorder(recorder);
+    let handle = engine
+        .execute_with(graph.clone(), "a1", "trigger", options)
+        .unwrap();
+    handle.join();
+    let recording = handle.recording().unwrap();
+    assert_eq!(recording.triggers.len(), 1);
+    assert_eq!(recording.dispatches.len(), 5);
+    assert_eq!(recording.values.len(), 3);
+
+    let path = std::env::temp_dir().join(format!("engine-recording-{}.json", std::process::id()));
+    recording.save(&path).unwrap();
+    let loaded = Recording::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded, recording);
+
+    let library = Arc::new(Library::get());
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut executor = executor(&library, &output);
+    let handle = executor.replay(graph, loaded).unwrap();
+    executor.run_until_idle();
+    match handle.outcome() {
+        Some(Outcome::Succeeded) => {}
+        outcome => panic!("unexpected outcome {:?}", outcome),
+    }
+
+    let mut values = recording.values.clone();
+    values.sort_by_key(|values| values.step);
+    let expected: String = values
+        .iter()
+        .map(|values| format!("{}\n", values.outputs["now"]))
+        .collect();
+    let output = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn replay_divergence() {
+    let library = Arc::new(Library::get());
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut executor = executor(&library, &output);
+
+    let recorder = Arc::new(Recorder::record());
+    let options = ExecutionOptions::new().recorder(recorder);
+    let graph = build_clock_graph(&library.schema, 3).unwrap();
+    let handle = executor.execute_actions_with(graph, options).unwrap();
+    executor.run_until_idle();
+    let recording = handle.recording().unwrap();
+
+    let graph = build_clock_graph(&library.schema, 4).unwrap();
+    let handle = executor.replay(graph, recording).unwrap();
+    executor.run_until_idle();
+    match handle.outcome() {
+        Some(Outcome::Failed(_)) => {}
+        outcome => panic!("unexpected outcome {:?}", outcome),
+    }
+}
+
+#[test]
+fn keep_nondeterministic() {
+    let library = Library::get();
+    let mut graph = build_clock_graph(&library.schema, 3).unwrap();
+
+    let report = fold_constants(&mut graph, &library).unwrap();
+    assert!(report.nodes.is_empty());
+    assert!(graph.nodes.contains_key("clock"));
+}
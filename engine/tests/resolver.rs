@@ -0,0 +1,70 @@
+use engine::library::Library;
+use engine::plan::ExecutionPlan;
+use engine::resolver::Resolver;
+use graph::graph::Graph;
+use graph::value::Value;
+
+mod common;
+
+#[test]
+fn resolve_shared_nodes_once() {
+    let library = Library::get();
+    let graph = {
+        let mut gb = Graph::builder(&library.schema);
+        let six = gb.node("integer", "six").unwrap();
+        let plus = gb.node("plus", "plus").unwrap();
+        let minus = gb.node("minus", "minus").unwrap();
+        let p1 = gb.node("printer", "p1").unwrap();
+        gb.assign(&six, "value", Value::Integer(6)).unwrap();
+        gb.connect(&six, "return-value", &plus, "a").unwrap();
+        gb.connect(&six, "return-value", &plus, "b").unwrap();
+        gb.connect(&plus, "c", &minus, "a").unwrap();
+        gb.connect(&six, "return-value", &minus, "b").unwrap();
+        gb.connect(&minus, "c", &p1, "content").unwrap();
+        gb.build().unwrap()
+    };
+    let plan = ExecutionPlan::compile(&graph, &library).unwrap();
+    let p1 = plan.get_node_index("p1").unwrap();
+    let content = plan.get_slot_index(p1, "content").unwrap();
+
+    let mut resolver = Resolver::new(&plan);
+    assert_eq!(resolver.resolve(content).unwrap(), Value::Integer(6));
+    assert_eq!(resolver.evaluations(), 3);
+    assert_eq!(resolver.resolve(content).unwrap(), Value::Integer(6));
+    assert_eq!(resolver.evaluations(), 3);
+}
+
+#[test]
+fn resolve_assigned_values() {
+    let library = Library::get();
+    let graph = common::build_graph(&library.schema).unwrap();
+    let plan = ExecutionPlan::compile(&graph, &library).unwrap();
+    let r1 = plan.get_node_index("r1").unwrap();
+    let p1 = plan.get_node_index("p1").unwrap();
+
+    let mut resolver = Resolver::new(&plan);
+    let times = plan.get_slot_index(r1, "times").unwrap();
+    assert_eq!(resolver.resolve(times).unwrap(), Value::Integer(3));
+    assert_eq!(resolver.evaluations(), 0);
+    let content = plan.get_slot_index(p1, "content").unwrap();
+    assert_eq!(resolver.resolve(content).unwrap(), Value::Integer(7));
+    assert_eq!(resolver.evaluations(), 5);
+}
+
+#[test]
+fn resolve_cycle() {
+    let library = Library::get();
+    let graph = {
+        let mut gb = Graph::builder(&library.schema);
+        let plus = gb.node("plus", "plus").unwrap();
+        let minus = gb.node("minus", "minus").unwrap();
+        gb.connect(&plus, "c", &minus, "a").unwrap();
+        gb.connect(&minus, "c", &plus, "a").unwrap();
+        gb.build().unwrap()
+    };
+    let plan = ExecutionPlan::compile(&graph, &library).unwrap();
+    let plus = plan.get_node_index("plus").unwrap();
+    let c = plan.get_slot_index(plus, "c").unwrap();
+
+    assert!(Resolver::new(&plan).resolve(c).is_err());
+}
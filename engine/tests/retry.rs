@@ -0,0 +1,190 @@
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use graph::graph::Graph;
+
+use engine::error::EngineError;
+use engine::execution::{ExecutionOptions, Outcome};
+use engine::library::Library;
+use engine::local::LocalExecutor;
+use engine::processor::action_processor::ActionProcessor;
+use engine::processor::repeat_processor::RepeatProcessor;
+use engine::processor::{Processor, Router};
+use engine::retry::RetryPolicy;
+use engine::trace::{MemorySink, TraceEvent};
+
+mod common;
+
+/// Handles `printer#print`, failing the first `failures` attempts of every instruction.
+struct FlakyPrinter {
+    router: Router,
+}
+
+impl FlakyPrinter {
+    fn new(library: &Arc<Library>, failures: u32) -> Self {
+        let print = library.get_command("printer", "print").unwrap();
+        let mut router = Router::new();
+        router.route(&print, move |invocation| {
+            if invocation.instruction().attempt <= failures {
+                Err(EngineError::with_code("io", "Printer is busy."))
+            } else {
+                Ok(())
+            }
+        });
+        FlakyPrinter { router }
+    }
+}
+
+impl Processor for FlakyPrinter {
+    fn router(&self) -> &Router {
+        &self.router
+    }
+}
+
+fn run(library: Library, failures: u32, options: ExecutionOptions) -> (Outcome, Vec<u32>) {
+    run_graph(library, failures, options, |_| {})
+}
+
+fn run_graph<F>(
+    library: Library,
+    failures: u32,
+    options: ExecutionOptions,
+    configure: F,
+) -> (Outcome, Vec<u32>)
+where
+    F: FnOnce(&mut Graph),
+{
+    let library = Arc::new(library);
+    let mut graph = common::build_graph(&library.schema).unwrap();
+    configure(&mut graph);
+    let processors: Vec<Box<dyn Processor>> = vec![
+        Box::new(ActionProcessor::new(&library).unwrap()),
+        Box::new(FlakyPrinter::new(&library, failures)),
+        Box::new(RepeatProcessor::new(&library).unwrap()),
+    ];
+    let sink = Arc::new(MemorySink::new());
+    let mut executor = LocalExecutor::with_processors(Arc::clone(&library), processors);
+    executor.set_trace_sink(sink.clone());
+
+    let handle = executor
+        .execute_with(graph, "a1", "trigger", options)
+        .unwrap();
+    executor.run_until_idle();
+    let mut attempts: Vec<u32> = sink
+        .records()
+        .into_iter()
+        .filter_map(|record| match record.event {
+            TraceEvent::Retrying { attempt, .. } => Some(attempt),
+            _ => None,
+        })
+        .collect();
+    attempts.sort_unstable();
+    (handle.outcome().unwrap(), attempts)
+}
+
+#[test]
+fn retry_schema_node() {
+    let mut library = Library::get();
+    library.set_retry_policy(
+        "printer",
+        RetryPolicy::new(3).fixed(Duration::from_millis(1)),
+    );
+
+    let (outcome, attempts) = run(library, 2, ExecutionOptions::new());
+    assert!(matches!(outcome, Outcome::Succeeded));
+    assert_eq!(attempts, vec![1, 1, 1, 2, 2, 2]);
+}
+
+#[test]
+fn exhausted() {
+    let mut library = Library::get();
+    library.set_retry_policy("printer", RetryPolicy::new(2));
+
+    let (outcome, attempts) = run(library, 2, ExecutionOptions::new());
+    match outcome {
+        Outcome::Failed(e) => assert_eq!(e.code.as_deref(), Some("io")),
+        outcome => panic!("unexpected outcome {:?}", outcome),
+    }
+    assert!(!attempts.is_empty());
+    assert!(attempts.iter().all(|attempt| *attempt == 1));
+}
+
+#[test]
+fn not_retriable() {
+    let mut library = Library::get();
+    library.set_retry_policy("printer", RetryPolicy::new(3).retry_on("timeout"));
+
+    let (outcome, attempts) = run(library, 1, ExecutionOptions::new());
+    assert!(matches!(outcome, Outcome::Failed(_)));
+    assert!(attempts.is_empty());
+}
+
+#[test]
+fn retry_placed_node() {
+    let mut library = Library::get();
+    library.set_retry_policy("printer", RetryPolicy::new(1));
+
+    let (outcome, attempts) = run_graph(library, 1, ExecutionOptions::new(), |graph| {
+        graph.set_retry_policy("p1", RetryPolicy::new(2)).unwrap()
+    });
+    assert!(matches!(outcome, Outcome::Succeeded));
+    assert_eq!(attempts, vec![1, 1, 1]);
+}
+
+#[test]
+fn override_in_execution() {
+    let mut library = Library::get();
+    library.set_retry_policy("printer", RetryPolicy::new(1));
+
+    let options = ExecutionOptions::new().retry_policy("p1", RetryPolicy::new(2));
+    let (outcome, attempts) = run(library, 1, options);
+    assert!(matches!(outcome, Outcome::Succeeded));
+    assert_eq!(attempts, vec![1, 1, 1]);
+}
+
+#[test]
+fn cancel_backoff() {
+    let mut library = Library::get();
+    library.set_retry_policy(
+        "printer",
+        RetryPolicy::new(2).fixed(Duration::from_secs(60)),
+    );
+    let library = Arc::new(library);
+    let graph = common::build_graph(&library.schema).unwrap();
+    let processors: Vec<Box<dyn Processor>> = vec![
+        Box::new(ActionProcessor::new(&library).unwrap()),
+        Box::new(FlakyPrinter::new(&library, 1)),
+        Box::new(RepeatProcessor::new(&library).unwrap()),
+    ];
+    let mut executor = LocalExecutor::with_processors(Arc::clone(&library), processors);
+
+    let handle = executor.execute(graph, "a1", "trigger").unwrap();
+    let cancelled = handle.clone();
+    let canceller = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(20));
+        cancelled.cancel();
+    });
+    let started = Instant::now();
+    executor.run_until_idle();
+    canceller.join().unwrap();
+    assert!(started.elapsed() < Duration::from_secs(10));
+    assert!(matches!(handle.outcome(), Some(Outcome::Cancelled)));
+}
+
+#[test]
+fn exponential_backoff() {
+    let ms = Duration::from_millis;
+    let policy = RetryPolicy::new(5).exponential(ms(10), ms(30));
+    assert_eq!(policy.delay(1), Some(ms(10)));
+    assert_eq!(policy.delay(2), Some(ms(20)));
+    assert_eq!(policy.delay(3), Some(ms(30)));
+    assert_eq!(policy.delay(4), Some(ms(30)));
+    assert_eq!(policy.delay(5), None);
+
+    let policy = policy.jitter(0.5);
+    for attempt in 1..5 {
+        let delay = policy.delay(attempt).unwrap();
+        assert!(delay <= ms(30) && delay >= ms(5));
+    }
+}
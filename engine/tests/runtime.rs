@@ -0,0 +1,174 @@
+#![cfg(feature = "tokio")]
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use graph::graph::Graph;
+
+use engine::error::EngineError;
+use engine::execution::Outcome;
+use engine::library::basic::printer;
+use engine::library::Library;
+use engine::retry::RetryPolicy;
+use engine::runtime::{AsyncEngine, AsyncRouter};
+use engine::trace::{MemorySink, TraceEvent};
+
+mod common;
+
+#[test]
+fn async_handler() {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_time()
+        .build()
+        .unwrap();
+    let library = Arc::new(Library::get());
+    let graph = common::build_graph(&library.schema).unwrap();
+
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let print_command = library
+        .get_command(printer::ID, printer::COMMAND_PRINT)
+        .unwrap();
+    let content_input = library
+        .get_input(printer::ID, printer::INPUT_CONTENT)
+        .unwrap();
+    let mut router = AsyncRouter::new();
+    let lines = Arc::clone(&output);
+    router.route(&print_command, move |invocation| {
+        let content = invocation.input(&content_input);
+        let lines = Arc::clone(&lines);
+        async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            lines.lock().unwrap().push(content?.to_string());
+            Ok(invocation)
+        }
+    });
+
+    let engine = AsyncEngine::new(Arc::clone(&library), router, runtime.handle().clone()).unwrap();
+    let handle = engine.execute_actions(graph).unwrap();
+    match runtime.block_on(handle.completion()) {
+        Outcome::Succeeded => {}
+        outcome => panic!("unexpected outcome {:?}", outcome),
+    }
+    assert_eq!(*output.lock().unwrap(), vec!["7", "7", "7"]);
+}
+
+#[test]
+fn async_failure() {
+    let runtime = tokio::runtime::Builder::new_multi_thread().build().unwrap();
+    let library = Arc::new(Library::get());
+    let graph = common::build_graph(&library.schema).unwrap();
+
+    let print_command = library
+        .get_command(printer::ID, printer::COMMAND_PRINT)
+        .unwrap();
+    let mut router = AsyncRouter::new();
+    router.route(&print_command, |_| async {
+        Err(EngineError::new("Printer is offline."))
+    });
+
+    let engine = AsyncEngine::new(Arc::clone(&library), router, runtime.handle().clone()).unwrap();
+    let handle = engine.execute_actions(graph).unwrap();
+    match runtime.block_on(handle.completion()) {
+        Outcome::Failed(_) => {}
+        outcome => panic!("unexpected outcome {:?}", outcome),
+    }
+}
+
+#[test]
+fn async_inputs_resolved_once() {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_time()
+        .build()
+        .unwrap();
+    let library = Arc::new(Library::get());
+    let graph = {
+        let mut gb = Graph::builder(&library.schema);
+        let a1 = gb.node("action", "a1").unwrap();
+        let p1 = gb.node("printer", "p1").unwrap();
+        let clock = gb.node("clock", "clock").unwrap();
+        gb.connect(&a1, "triggered", &p1, "print").unwrap();
+        gb.connect(&clock, "now", &p1, "content").unwrap();
+        gb.build().unwrap()
+    };
+
+    let values = Arc::new(Mutex::new(Vec::new()));
+    let print_command = library
+        .get_command(printer::ID, printer::COMMAND_PRINT)
+        .unwrap();
+    let content_input = library
+        .get_input(printer::ID, printer::INPUT_CONTENT)
+        .unwrap();
+    let mut router = AsyncRouter::new();
+    let read = Arc::clone(&values);
+    router.route(&print_command, move |invocation| {
+        let content_input = content_input.clone();
+        let read = Arc::clone(&read);
+        async move {
+            let first = invocation.input(&content_input)?;
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            let second = invocation.input(&content_input)?;
+            read.lock().unwrap().extend(vec![first, second]);
+            Ok(invocation)
+        }
+    });
+
+    let engine = AsyncEngine::new(Arc::clone(&library), router, runtime.handle().clone()).unwrap();
+    let handle = engine.execute_actions(graph).unwrap();
+    assert!(matches!(
+        runtime.block_on(handle.completion()),
+        Outcome::Succeeded
+    ));
+    let values = values.lock().unwrap();
+    assert_eq!(values.len(), 2);
+    assert_eq!(values[0], values[1]);
+}
+
+#[test]
+fn async_retry() {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_time()
+        .build()
+        .unwrap();
+    let mut library = Library::get();
+    library.set_retry_policy(
+        printer::ID,
+        RetryPolicy::new(3).fixed(Duration::from_millis(5)),
+    );
+    let library = Arc::new(library);
+    let graph = common::build_graph(&library.schema).unwrap();
+
+    let print_command = library
+        .get_command(printer::ID, printer::COMMAND_PRINT)
+        .unwrap();
+    let calls = Arc::new(AtomicU32::new(0));
+    let mut router = AsyncRouter::new();
+    let counted = Arc::clone(&calls);
+    router.route(&print_command, move |invocation| {
+        let call = counted.fetch_add(1, Ordering::SeqCst);
+        async move {
+            if call < 2 {
+                Err(EngineError::with_code("io", "Printer is busy."))
+            } else {
+                Ok(invocation)
+            }
+        }
+    });
+
+    let sink = Arc::new(MemorySink::new());
+    let mut engine =
+        AsyncEngine::new(Arc::clone(&library), router, runtime.handle().clone()).unwrap();
+    engine.set_trace_sink(sink.clone()).unwrap();
+    let handle = engine.execute_actions(graph).unwrap();
+    assert!(matches!(
+        runtime.block_on(handle.completion()),
+        Outcome::Succeeded
+    ));
+    assert_eq!(calls.load(Ordering::SeqCst), 5);
+    let retries = sink
+        .records()
+        .into_iter()
+        .filter(|record| matches!(record.event, TraceEvent::Retrying { .. }))
+        .count();
+    assert_eq!(retries, 2);
+}
@@ -0,0 +1,261 @@
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use engine::execution::{Execution, ExecutionOptions, Outcome, Priority};
+use engine::library::{Library, Values};
+use engine::message::{Instruction, Message};
+use engine::plan::ExecutionPlan;
+use engine::scheduler::{Scheduler, SchedulerConfig, SchedulingPolicy};
+use engine::{Engine, EngineConfig};
+
+mod common;
+
+fn executions(priorities: &[Priority]) -> Vec<Arc<Execution>> {
+    let library = Library::get();
+    let graph = common::build_graph(&library.schema).unwrap();
+    let plan = Arc::new(ExecutionPlan::compile(&graph, &library).unwrap());
+    priorities
+        .iter()
+        .enumerate()
+        .map(|(id, priority)| {
+            let options = ExecutionOptions::new().priority(*priority);
+            Arc::new(Execution::with_options(
+                id as u64,
+                Arc::clone(&plan),
+                options,
+            ))
+        })
+        .collect()
+}
+
+fn instruction(execution: &Arc<Execution>) -> Instruction {
+    let trigger = execution.plan().find_command("a1", "trigger").unwrap();
+    Instruction::new(Arc::clone(execution), trigger, Values::new())
+}
+
+fn order(scheduler: &Scheduler) -> Vec<u64> {
+    std::iter::from_fn(|| scheduler.try_pop())
+        .map(|instruction| instruction.context.execution.id())
+        .collect()
+}
+
+#[test]
+fn round_robin() {
+    let scheduler = Scheduler::new(SchedulerConfig::default());
+    let executions = executions(&[0, 0, 2]);
+    for (execution, count) in executions.iter().zip(&[3, 2, 2]) {
+        for _ in 0..*count {
+            scheduler.push(instruction(execution));
+        }
+    }
+
+    assert_eq!(scheduler.len(), 7);
+    assert_eq!(order(&scheduler), vec![2, 2, 0, 1, 0, 1, 0]);
+    assert!(scheduler.is_empty());
+}
+
+#[test]
+fn weighted_fair() {
+    let scheduler = Scheduler::new(SchedulerConfig {
+        policy: SchedulingPolicy::WeightedFair,
+        ..SchedulerConfig::default()
+    });
+    let executions = executions(&[0, 1]);
+    for _ in 0..3 {
+        scheduler.push(instruction(&executions[0]));
+    }
+    for _ in 0..4 {
+        scheduler.push(instruction(&executions[1]));
+    }
+
+    assert_eq!(order(&scheduler), vec![0, 1, 1, 0, 1, 1, 0]);
+}
+
+#[test]
+fn work_stealing() {
+    let scheduler = Scheduler::new(SchedulerConfig {
+        policy: SchedulingPolicy::WorkStealing,
+        ..SchedulerConfig::default()
+    });
+    let executions = executions(&[0, 0]);
+    let busy = scheduler.local_queue().unwrap();
+    let idle = scheduler.local_queue().unwrap();
+    scheduler.push_local(&busy, instruction(&executions[0]));
+    scheduler.push_local(&busy, instruction(&executions[0]));
+    scheduler.push(instruction(&executions[1]));
+
+    assert_eq!(scheduler.len(), 3);
+    let ids: Vec<u64> = (0..3)
+        .map(|_| match scheduler.pop_local(&idle) {
+            Message::Instruction(instruction) => instruction.context.execution.id(),
+            Message::Stop => panic!("unexpected stop"),
+        })
+        .collect();
+    assert_eq!(ids, vec![1, 0, 0]);
+    assert!(scheduler.is_empty());
+    assert!(Scheduler::new(SchedulerConfig::default())
+        .local_queue()
+        .is_none());
+}
+
+#[test]
+fn work_stealing_count() {
+    const INSTRUCTIONS: usize = 10_000;
+    let scheduler = Arc::new(Scheduler::new(SchedulerConfig {
+        policy: SchedulingPolicy::WorkStealing,
+        ..SchedulerConfig::default()
+    }));
+    let executions = executions(&[0]);
+    let instructions: Vec<_> = (0..INSTRUCTIONS)
+        .map(|_| instruction(&executions[0]))
+        .collect();
+    let stealer = {
+        let scheduler = Arc::clone(&scheduler);
+        thread::spawn(move || {
+            let mut taken = 0;
+            while taken < INSTRUCTIONS {
+                assert!(scheduler.len() <= INSTRUCTIONS);
+                if scheduler.try_pop().is_some() {
+                    taken += 1;
+                }
+            }
+        })
+    };
+    for instruction in instructions {
+        scheduler.push(instruction);
+    }
+    stealer.join().unwrap();
+    assert!(scheduler.is_empty());
+}
+
+#[test]
+fn stop_first() {
+    let scheduler = Scheduler::new(SchedulerConfig::default());
+    let executions = executions(&[0]);
+    scheduler.push(instruction(&executions[0]));
+    scheduler.stop(1);
+
+    assert!(matches!(scheduler.pop(), Message::Stop));
+    assert!(matches!(scheduler.pop(), Message::Instruction(_)));
+}
+
+#[test]
+fn clear_stops() {
+    let scheduler = Scheduler::new(SchedulerConfig::default());
+    let executions = executions(&[0]);
+    scheduler.stop(2);
+    assert!(matches!(scheduler.pop(), Message::Stop));
+    scheduler.clear_stops();
+    scheduler.push(instruction(&executions[0]));
+
+    assert!(matches!(scheduler.pop(), Message::Instruction(_)));
+}
+
+#[test]
+fn backpressure() {
+    let scheduler = Arc::new(Scheduler::new(SchedulerConfig {
+        max_queued_per_execution: Some(2),
+        ..SchedulerConfig::default()
+    }));
+    let executions = executions(&[0, 0]);
+    // Without workers to drain it, the queue grows past its limit.
+    for _ in 0..3 {
+        scheduler.push(instruction(&executions[0]));
+    }
+    scheduler.push(instruction(&executions[1]));
+    assert_eq!(order(&scheduler), vec![0, 1, 0, 0]);
+
+    scheduler.set_workers(1);
+    scheduler.push(instruction(&executions[0]));
+    scheduler.push(instruction(&executions[0]));
+    let producer = {
+        let scheduler = Arc::clone(&scheduler);
+        let instruction = instruction(&executions[0]);
+        thread::spawn(move || scheduler.push(instruction))
+    };
+    thread::sleep(Duration::from_millis(50));
+    assert!(!producer.is_finished());
+    assert_eq!(scheduler.len(), 2);
+    assert!(scheduler.try_pop().is_some());
+    producer.join().unwrap();
+    assert_eq!(scheduler.len(), 2);
+
+    let library = Library::get();
+    let graph = common::build_graph(&library.schema).unwrap();
+    let mut config = EngineConfig::load().unwrap();
+    config.scheduler.max_queued = Some(1);
+    config.scheduler.max_queued_per_execution = Some(1);
+    let mut engine = Engine::new(config, library);
+    engine.execute(graph.clone(), "a1", "trigger").unwrap();
+    assert!(engine.execute(graph.clone(), "a1", "trigger").is_err());
+
+    engine.run();
+    let handles: Vec<_> = (0..10)
+        .map(|_| engine.execute(graph.clone(), "a1", "trigger").unwrap())
+        .collect();
+    for handle in handles {
+        match handle.join_timeout(Duration::from_secs(10)) {
+            Some(Outcome::Succeeded) => {}
+            outcome => panic!("unexpected outcome {:?}", outcome),
+        }
+    }
+}
+
+#[test]
+fn many_executions() {
+    for policy in [
+        SchedulingPolicy::RoundRobin,
+        SchedulingPolicy::WeightedFair,
+        SchedulingPolicy::WorkStealing,
+    ]
+    .iter()
+    {
+        run_executions(*policy);
+    }
+}
+
+fn run_executions(policy: SchedulingPolicy) {
+    let library = Library::get();
+    let graph = common::build_graph(&library.schema).unwrap();
+    let mut config = EngineConfig::load().unwrap();
+    config.scheduler.policy = policy;
+    let mut engine = Engine::new(config, library);
+
+    engine.run();
+    let handles: Vec<_> = (0..50)
+        .map(|_| engine.execute(graph.clone(), "a1", "trigger").unwrap())
+        .collect();
+    for handle in handles {
+        match handle.join_timeout(Duration::from_secs(10)) {
+            Some(Outcome::Succeeded) => {}
+            outcome => panic!("unexpected outcome {:?}", outcome),
+        }
+    }
+}
+
+#[test]
+fn delayed() {
+    for policy in [SchedulingPolicy::RoundRobin, SchedulingPolicy::WorkStealing] {
+        let scheduler = Scheduler::new(SchedulerConfig {
+            policy,
+            ..SchedulerConfig::default()
+        });
+        let executions = executions(&[0, 0]);
+        let mut retry = instruction(&executions[0]);
+        retry.not_before = Some(Instant::now() + Duration::from_millis(50));
+        scheduler.push(retry);
+        scheduler.push(instruction(&executions[1]));
+
+        assert_eq!(scheduler.len(), 1);
+        assert_eq!(order(&scheduler), vec![1]);
+        let started = Instant::now();
+        match scheduler.pop() {
+            Message::Instruction(instruction) => {
+                assert_eq!(instruction.context.execution.id(), 0)
+            }
+            Message::Stop => panic!("unexpected stop"),
+        }
+        assert!(started.elapsed() >= Duration::from_millis(40));
+    }
+}
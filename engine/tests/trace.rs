@@ -0,0 +1,106 @@
+use std::fs;
+use std::sync::Arc;
+
+use graph::value::Value;
+
+use engine::library::Library;
+use engine::local::LocalExecutor;
+use engine::trace::{JsonLinesSink, MemorySink, TraceEvent};
+use engine::{Engine, EngineConfig};
+
+mod common;
+
+#[test]
+fn memory() {
+    let library = Arc::new(Library::get());
+    let graph = common::build_graph(&library.schema).unwrap();
+    let sink = Arc::new(MemorySink::new());
+    let mut executor = LocalExecutor::new(Arc::clone(&library)).unwrap();
+    executor.set_trace_sink(sink.clone());
+
+    let handle = executor.execute(graph, "a1", "trigger").unwrap();
+    executor.run_until_idle();
+
+    let records = sink.records();
+    assert!(records.iter().all(|record| record.execution == handle.id()));
+    let events: Vec<TraceEvent> = records.into_iter().map(|record| record.event).collect();
+    assert_eq!(
+        &events[..7],
+        &[
+            TraceEvent::Dispatched {
+                node: "a1".into(),
+                command: "trigger".into()
+            },
+            TraceEvent::CommandStarted {
+                node: "a1".into(),
+                command: "trigger".into()
+            },
+            TraceEvent::CommandFinished {
+                node: "a1".into(),
+                command: "trigger".into()
+            },
+            TraceEvent::EventFired {
+                node: "a1".into(),
+                event: "triggered".into()
+            },
+            TraceEvent::EdgeFollowed {
+                edge: "a1#triggered>r1#start".into()
+            },
+            TraceEvent::Dispatched {
+                node: "r1".into(),
+                command: "start".into()
+            },
+            TraceEvent::CommandStarted {
+                node: "r1".into(),
+                command: "start".into()
+            },
+        ]
+    );
+    assert_eq!(
+        events[7],
+        TraceEvent::InputResolved {
+            node: "r1".into(),
+            input: "times".into(),
+            value: Value::Integer(3)
+        }
+    );
+    let printed = events
+        .iter()
+        .filter(|event| match event {
+            TraceEvent::InputResolved { node, value, .. } => {
+                node == "p1" && *value == Value::Integer(7)
+            }
+            _ => false,
+        })
+        .count();
+    assert_eq!(printed, 3);
+}
+
+#[test]
+fn json_lines() {
+    let library = Library::get();
+    let graph = common::build_graph(&library.schema).unwrap();
+    let path = std::env::temp_dir().join(format!("engine-trace-{}.jsonl", std::process::id()));
+    let mut engine = Engine::new(EngineConfig::load().unwrap(), library);
+    engine.set_trace_sink(Arc::new(JsonLinesSink::create(&path).unwrap()));
+
+    engine.run();
+    let handle = engine.execute(graph, "a1", "trigger").unwrap();
+    handle.join();
+    drop(engine);
+
+    let content = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    let lines: Vec<serde_json::Value> = content
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines[0]["type"], "dispatched");
+    assert!(lines[0]["worker"].is_null());
+    assert!(lines
+        .iter()
+        .any(|line| line["type"] == "command_started" && line["worker"].is_u64()));
+    assert!(lines
+        .iter()
+        .all(|line| line["execution"] == handle.id() && line["timestamp"].is_u64()));
+}
@@ -31,7 +31,7 @@ impl Display for GraphError {
     }
 }
 
-impl<'a> error::Error for GraphError {
+impl error::Error for GraphError {
     fn description(&self) -> &str {
         self.message.as_str()
     }
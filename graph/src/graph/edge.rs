@@ -3,6 +3,7 @@
 use std::collections::HashMap;
 use std::fmt::{Display, Error, Formatter};
 
+use crate::graph::guard::Guard;
 use crate::graph::placed_node::PlacedNode;
 use crate::schema::property::Property;
 
@@ -22,10 +23,12 @@ pub struct Edge {
     pub source: Hook,
     /// Target hook.
     pub target: Hook,
+    /// Condition that must hold for an event to trigger a command.
+    pub guard: Option<Guard>,
 }
 
 /// Contains edges of a graph.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct EdgeMap {
     /// All edges by generated key.
     pub edges: HashMap<String, Edge>,
@@ -49,7 +52,20 @@ impl Display for Hook {
 impl Edge {
     /// Constructs an `Edge`.
     pub fn new(source: Hook, target: Hook) -> Self {
-        Edge { source, target }
+        Edge {
+            source,
+            target,
+            guard: None,
+        }
+    }
+
+    /// Constructs an `Edge` that is only followed when a guard holds.
+    pub fn with_guard(source: Hook, target: Hook, guard: Guard) -> Self {
+        Edge {
+            source,
+            target,
+            guard: Some(guard),
+        }
     }
 }
 
@@ -91,7 +107,55 @@ impl EdgeMap {
 
     /// Inserts an edge. Returns old value if it existed.
     pub fn insert(&mut self, edge: &Edge) -> Option<Edge> {
-        self.edges.insert(edge.to_string(), edge.clone())
+        let old = self.remove(edge);
+        if edge.target.property.is_input() {
+            self.inputs
+                .insert(edge.target.to_string(), edge.source.clone());
+        }
+        self.outputs
+            .entry(edge.source.to_string())
+            .or_default()
+            .push(edge.target.clone());
+        self.edges.insert(edge.to_string(), edge.clone());
+        old
+    }
+
+    /// Removes an edge. Returns removed edge if it existed.
+    pub fn remove(&mut self, edge: &Edge) -> Option<Edge> {
+        let removed = self.edges.remove(edge.to_string().as_str())?;
+        let source_key = removed.source.to_string();
+        let target_key = removed.target.to_string();
+        if removed.target.property.is_input() {
+            // Another edge may still feed the input, if inputs are connected more than once.
+            match self
+                .edges
+                .values()
+                .find(|edge| edge.target.to_string() == target_key)
+            {
+                Some(edge) => {
+                    self.inputs.insert(target_key.clone(), edge.source.clone());
+                }
+                None => {
+                    self.inputs.remove(target_key.as_str());
+                }
+            }
+        }
+        if let Some(targets) = self.outputs.get_mut(source_key.as_str()) {
+            targets.retain(|target| target.to_string() != target_key);
+            if targets.is_empty() {
+                self.outputs.remove(source_key.as_str());
+            }
+        }
+        Some(removed)
+    }
+
+    /// Returns all edges whose source or target is a node with given key.
+    pub fn get_node_edges(&self, key: &str) -> Vec<Edge> {
+        self.edges
+            .values()
+            .filter(|edge| edge.source.node.key == key || edge.target.node.key == key)
+            .cloned()
+            .collect()
     }
 
     /// Returns an input hook for a target if it exists.
@@ -122,13 +186,3 @@ impl EdgeMap {
         )
     }
 }
-
-impl Default for EdgeMap {
-    fn default() -> Self {
-        EdgeMap {
-            edges: Default::default(),
-            inputs: Default::default(),
-            outputs: Default::default(),
-        }
-    }
-}
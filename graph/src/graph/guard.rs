@@ -0,0 +1,188 @@
+//! Guard is a condition that must hold for an edge to be followed.
+
+use std::fmt::{Display, Error, Formatter};
+
+use crate::error::GraphError;
+use crate::value::{DataType, Value};
+
+/// Comparison performed by a guard.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Operator {
+    /// `==`
+    Equal,
+    /// `!=`
+    NotEqual,
+    /// `<`
+    Less,
+    /// `<=`
+    LessOrEqual,
+    /// `>`
+    Greater,
+    /// `>=`
+    GreaterOrEqual,
+}
+
+/// Compares a value of an event's payload field or a node's output to a constant.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Guard {
+    /// Id of a payload field or an output.
+    pub operand: String,
+    /// Comparison operator.
+    pub operator: Operator,
+    /// Constant to compare to.
+    pub value: Value,
+}
+
+impl Operator {
+    const ALL: [Operator; 6] = [
+        Operator::Equal,
+        Operator::NotEqual,
+        Operator::LessOrEqual,
+        Operator::GreaterOrEqual,
+        Operator::Less,
+        Operator::Greater,
+    ];
+
+    /// Returns operator's symbol.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Operator::Equal => "==",
+            Operator::NotEqual => "!=",
+            Operator::Less => "<",
+            Operator::LessOrEqual => "<=",
+            Operator::Greater => ">",
+            Operator::GreaterOrEqual => ">=",
+        }
+    }
+
+    /// Returns whether operator can compare values of a data type.
+    pub fn supports(self, data_type: DataType) -> bool {
+        match self {
+            Operator::Equal | Operator::NotEqual => true,
+            _ => data_type != DataType::Boolean,
+        }
+    }
+}
+
+impl Guard {
+    /// Constructs a `Guard`.
+    pub fn new(operand: &str, operator: Operator, value: Value) -> Self {
+        Guard {
+            operand: String::from(operand),
+            operator,
+            value,
+        }
+    }
+
+    /// Parses a guard from an expression such as `count > 2`.
+    ///
+    /// Constant can be an integer, a float, `true`, `false` or a double-quoted string.
+    pub fn parse(expression: &str) -> Result<Self, GraphError> {
+        let invalid = || GraphError::from(format!("Invalid guard '{}'.", expression));
+        let (index, operator) = Operator::ALL
+            .iter()
+            .filter_map(|operator| {
+                expression
+                    .find(operator.symbol())
+                    .map(|index| (index, *operator))
+            })
+            .min_by_key(|(index, _)| *index)
+            .ok_or_else(invalid)?;
+
+        let operand = expression[..index].trim();
+        if operand.is_empty()
+            || !operand
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(invalid());
+        }
+        let value = parse_value(expression[index + operator.symbol().len()..].trim())
+            .ok_or_else(invalid)?;
+
+        let guard = Guard::new(operand, operator, value);
+        if !operator.supports(guard.value.data_type()) {
+            return Err(GraphError::from(format!(
+                "Operator '{}' cannot compare {:?} values.",
+                operator.symbol(),
+                guard.value.data_type()
+            )));
+        }
+        Ok(guard)
+    }
+
+    /// Returns whether an operand's value satisfies this guard.
+    pub fn evaluate(&self, operand: &Value) -> Result<bool, GraphError> {
+        if operand.data_type() != self.value.data_type() {
+            return Err(GraphError::from(format!(
+                "Guard '{}' cannot compare {:?}.",
+                self, operand
+            )));
+        }
+        Ok(match self.operator {
+            Operator::Equal => *operand == self.value,
+            Operator::NotEqual => *operand != self.value,
+            Operator::Less => *operand < self.value,
+            Operator::LessOrEqual => *operand <= self.value,
+            Operator::Greater => *operand > self.value,
+            Operator::GreaterOrEqual => *operand >= self.value,
+        })
+    }
+}
+
+impl Display for Guard {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "{} {} ", self.operand, self.operator.symbol())?;
+        match &self.value {
+            Value::Integer(value) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{:?}", value),
+            Value::Boolean(value) => write!(f, "{}", value),
+            Value::String(value) => write!(f, "{:?}", value),
+        }
+    }
+}
+
+fn parse_value(s: &str) -> Option<Value> {
+    if s.len() >= 2 && s.starts_with('"') && s.ends_with('"') {
+        return Some(Value::from(&s[1..s.len() - 1]));
+    }
+    match s {
+        "true" => Some(Value::from(true)),
+        "false" => Some(Value::from(false)),
+        _ => s
+            .parse::<i64>()
+            .map(Value::from)
+            .or_else(|_| s.parse::<f64>().map(Value::from))
+            .ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let guard = Guard::parse("count > 2").unwrap();
+        assert_eq!(
+            guard,
+            Guard::new("count", Operator::Greater, Value::from(2))
+        );
+        assert_eq!(guard.to_string(), "count > 2");
+        assert!(guard.evaluate(&Value::from(3)).unwrap());
+        assert!(!guard.evaluate(&Value::from(2)).unwrap());
+        assert!(guard.evaluate(&Value::from("3")).is_err());
+
+        let guard = Guard::parse("return-value<=1.5").unwrap();
+        assert_eq!(guard.operator, Operator::LessOrEqual);
+        assert_eq!(guard.value, Value::from(1.5));
+
+        let guard = Guard::parse("name != \"a b\"").unwrap();
+        assert!(guard.evaluate(&Value::from("c")).unwrap());
+
+        assert!(Guard::parse("count").is_err());
+        assert!(Guard::parse("> 2").is_err());
+        assert!(Guard::parse("count > x").is_err());
+        assert!(Guard::parse("flag < true").is_err());
+    }
+}
@@ -4,13 +4,19 @@ use std::collections::HashMap;
 
 use crate::error::GraphError;
 use crate::graph::edge::{Edge, EdgeMap, Hook};
+use crate::graph::guard::Guard;
 use crate::graph::placed_node::PlacedNode;
+use crate::graph::port::Port;
 use crate::graph::property_value::PropertyValue;
+use crate::retry::RetryPolicy;
+use crate::schema::property::Property;
 use crate::schema::Schema;
 use crate::value::Value;
 
 pub mod edge;
+pub mod guard;
 pub mod placed_node;
+pub mod port;
 pub mod property_value;
 
 /// Represents a graph.
@@ -20,11 +26,15 @@ pub struct Graph {
     pub nodes: HashMap<String, PlacedNode>,
     /// Graph's edges.
     pub edge_map: EdgeMap,
+    /// Values accepted by the graph, bound to inputs of nodes.
+    pub parameters: HashMap<String, Port>,
+    /// Values produced by the graph, bound to data properties of nodes.
+    pub results: HashMap<String, Port>,
 }
 
 impl Graph {
     /// Constructs a `GraphBuilder`.
-    pub fn builder(schema: &Schema) -> GraphBuilder {
+    pub fn builder(schema: &Schema) -> GraphBuilder<'_> {
         GraphBuilder::new(schema)
     }
 
@@ -34,6 +44,77 @@ impl Graph {
     pub fn get_node(&self, key: &str) -> &PlacedNode {
         self.nodes.get(key).unwrap()
     }
+
+    /// Assigns a value to a property of a node with given key.
+    pub fn assign(&mut self, key: &str, property_id: &str, value: Value) -> Result<(), GraphError> {
+        let placed_node = match self.nodes.get_mut(key) {
+            Some(placed_node) => placed_node,
+            None => return Err(GraphError::from(format!("Node '{}' not found.", key))),
+        };
+        let property = placed_node.node.properties.get(property_id);
+        if property.is_none() {
+            return Err(GraphError::from(format!(
+                "Node property '{}' not found for '{}'",
+                property_id, placed_node.node.id
+            )));
+        }
+        let data_type = property.unwrap().data_type();
+        if data_type.is_none() {
+            return Err(GraphError::new(
+                "Can only assign values to data properties.",
+            ));
+        }
+        if *data_type.unwrap() != value.data_type() {
+            return Err(GraphError::new("Incompatible types."));
+        }
+
+        placed_node.values.insert(
+            String::from(property_id),
+            PropertyValue::new(property_id, value),
+        );
+
+        Ok(())
+    }
+
+    /// Sets a policy for retrying failed commands of a node with given key.
+    pub fn set_retry_policy(&mut self, key: &str, policy: RetryPolicy) -> Result<(), GraphError> {
+        match self.nodes.get_mut(key) {
+            Some(placed_node) => {
+                placed_node.retry = Some(policy);
+                Ok(())
+            }
+            None => Err(GraphError::from(format!("Node '{}' not found.", key))),
+        }
+    }
+
+    /// Assigns arguments to inputs bound to parameters.
+    pub fn bind(&mut self, arguments: &HashMap<String, Value>) -> Result<(), GraphError> {
+        if let Some(id) = arguments
+            .keys()
+            .find(|id| !self.parameters.contains_key(*id))
+        {
+            return Err(GraphError::from(format!("Unknown parameter '{}'.", id)));
+        }
+        let parameters: Vec<Port> = self.parameters.values().cloned().collect();
+        for parameter in parameters {
+            let value = arguments.get(&parameter.id).ok_or_else(|| {
+                GraphError::from(format!("Missing argument for '{}'.", parameter.id))
+            })?;
+            self.assign(&parameter.key, &parameter.property_id, value.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Removes a node with all of its edges and ports. Returns removed node if it existed.
+    pub fn remove_node(&mut self, key: &str) -> Option<PlacedNode> {
+        let placed_node = self.nodes.remove(key)?;
+        for edge in self.edge_map.get_node_edges(key) {
+            self.edge_map.remove(&edge);
+        }
+        self.parameters.retain(|_, port| port.key != key);
+        self.results.retain(|_, port| port.key != key);
+        Some(placed_node)
+    }
 }
 
 /// Utility for building graphs.
@@ -49,6 +130,8 @@ impl<'a> GraphBuilder<'a> {
             graph: Graph {
                 nodes: Default::default(),
                 edge_map: EdgeMap::default(),
+                parameters: Default::default(),
+                results: Default::default(),
             },
         }
     }
@@ -78,37 +161,58 @@ impl<'a> GraphBuilder<'a> {
         property_id: &str,
         value: Value,
     ) -> Result<(), GraphError> {
-        let property = placed_node.node.properties.get(property_id);
-        if property.is_none() {
-            return Err(GraphError::from(format!(
-                "Node property '{}' not found for '{}'",
-                property_id, placed_node.node.id
-            )));
-        }
-        let property = property.unwrap().clone();
-        let data_type = property.data_type();
-        if data_type.is_none() {
-            return Err(GraphError::new(
-                "Can only assign values to data properties.",
-            ));
-        }
-        if *data_type.unwrap() != value.data_type() {
-            return Err(GraphError::new("Incompatible types."));
-        }
+        self.graph.assign(&placed_node.key, property_id, value)
+    }
 
-        let mut values = placed_node.values.clone();
-        values.insert(
-            String::from(property_id),
-            PropertyValue::new(property_id, value.clone()),
-        );
-        self.graph.nodes.insert(
-            placed_node.key.clone(),
-            PlacedNode {
-                values,
-                ..placed_node.clone()
-            },
+    /// Sets a policy for retrying failed commands of a node.
+    pub fn retry(
+        &mut self,
+        placed_node: &PlacedNode,
+        policy: RetryPolicy,
+    ) -> Result<(), GraphError> {
+        self.graph.set_retry_policy(&placed_node.key, policy)
+    }
+
+    /// Declares a graph's parameter bound to a node's input.
+    pub fn parameter(
+        &mut self,
+        id: &str,
+        placed_node: &PlacedNode,
+        input_id: &str,
+    ) -> Result<(), GraphError> {
+        let property = placed_node.get_property(input_id);
+        if !property.is_input() {
+            return Err(GraphError::new("Parameters can only be bound to inputs."));
+        }
+        let port = Port::new(
+            id,
+            *property.data_type().unwrap(),
+            &placed_node.key,
+            input_id,
         );
+        if self.graph.parameters.contains_key(id) {
+            return Err(GraphError::from(format!("Duplicate parameter '{}'.", id)));
+        }
+        self.graph.parameters.insert(String::from(id), port);
+        Ok(())
+    }
 
+    /// Declares a graph's result bound to a node's input or output.
+    pub fn result(
+        &mut self,
+        id: &str,
+        placed_node: &PlacedNode,
+        property_id: &str,
+    ) -> Result<(), GraphError> {
+        let data_type = placed_node
+            .get_property(property_id)
+            .data_type()
+            .ok_or_else(|| GraphError::new("Results can only be bound to data properties."))?;
+        let port = Port::new(id, *data_type, &placed_node.key, property_id);
+        if self.graph.results.contains_key(id) {
+            return Err(GraphError::from(format!("Duplicate result '{}'.", id)));
+        }
+        self.graph.results.insert(String::from(id), port);
         Ok(())
     }
 
@@ -119,6 +223,74 @@ impl<'a> GraphBuilder<'a> {
         source_property_id: &str,
         target_node: &PlacedNode,
         target_property_id: &str,
+    ) -> Result<(), GraphError> {
+        self.insert_edge(
+            source_node,
+            source_property_id,
+            target_node,
+            target_property_id,
+            None,
+        )
+    }
+
+    /// Connects an event to a command by an edge that is only followed when a guard holds.
+    ///
+    /// Guard's operand must be a payload field of the event or an output of the source node.
+    pub fn connect_with_guard(
+        &mut self,
+        source_node: &PlacedNode,
+        source_property_id: &str,
+        target_node: &PlacedNode,
+        target_property_id: &str,
+        guard: &str,
+    ) -> Result<(), GraphError> {
+        let guard = Guard::parse(guard)?;
+        let event = match source_node.get_property(source_property_id) {
+            Property::Event(event) => event,
+            _ => return Err(GraphError::new("Only events can be guarded.")),
+        };
+        let data_type = event
+            .get_field(&guard.operand)
+            .map(|field| field.data_type)
+            .or_else(|| {
+                source_node
+                    .node
+                    .properties
+                    .get(&guard.operand)
+                    .filter(|property| property.is_output())
+                    .and_then(|property| property.data_type().copied())
+            })
+            .ok_or_else(|| {
+                GraphError::from(format!(
+                    "Guard operand '{}' is neither a payload field of '{}' nor an output of '{}'.",
+                    guard.operand, event.id, source_node.key
+                ))
+            })?;
+        if data_type != guard.value.data_type() {
+            return Err(GraphError::from(format!(
+                "Guard '{}' compares {:?} operand to {:?}.",
+                guard,
+                data_type,
+                guard.value.data_type()
+            )));
+        }
+
+        self.insert_edge(
+            source_node,
+            source_property_id,
+            target_node,
+            target_property_id,
+            Some(guard),
+        )
+    }
+
+    fn insert_edge(
+        &mut self,
+        source_node: &PlacedNode,
+        source_property_id: &str,
+        target_node: &PlacedNode,
+        target_property_id: &str,
+        guard: Option<Guard>,
     ) -> Result<(), GraphError> {
         let source_property = source_node.get_property(source_property_id);
         let target_property = target_node.get_property(target_property_id);
@@ -144,11 +316,34 @@ impl<'a> GraphBuilder<'a> {
         if source_property.data_type() != target_property.data_type() {
             return Err(GraphError::new("Incompatible types."));
         }
+        if let (Property::Event(event), Property::Command(command)) =
+            (source_property, target_property)
+        {
+            for parameter in command.parameters.iter() {
+                match event.get_field(&parameter.id) {
+                    Some(field) if field.data_type == parameter.data_type => {}
+                    Some(_) => {
+                        return Err(GraphError::from(format!(
+                            "Incompatible types for parameter '{}'.",
+                            parameter.id
+                        )));
+                    }
+                    None => {
+                        return Err(GraphError::from(format!(
+                            "Event '{}' does not provide parameter '{}'.",
+                            event.id, parameter.id
+                        )));
+                    }
+                }
+            }
+        }
 
-        let edge = Edge::new(
-            Hook::new(source_node.clone(), source_property.clone()),
-            Hook::new(target_node.clone(), target_property.clone()),
-        );
+        let source = Hook::new(source_node.clone(), source_property.clone());
+        let target = Hook::new(target_node.clone(), target_property.clone());
+        let edge = match guard {
+            Some(guard) => Edge::with_guard(source, target, guard),
+            None => Edge::new(source, target),
+        };
 
         if self.graph.edge_map.contains_edge(&edge) {
             return Err(GraphError::from(format!("Edge '{}' already exists.", edge)));
@@ -174,6 +369,20 @@ impl<'a> GraphBuilder<'a> {
             }
         }
 
+        for parameter in self.graph.parameters.values() {
+            let placed_node = self.graph.get_node(&parameter.key);
+            let input = Hook::new(
+                placed_node.clone(),
+                placed_node.get_property(&parameter.property_id).clone(),
+            );
+            if self.graph.edge_map.get_input(&input).is_some() {
+                return Err(GraphError::from(format!(
+                    "Parameter '{}' is bound to connected input '{}'.",
+                    parameter.id, input
+                )));
+            }
+        }
+
         Ok(self.graph.clone())
     }
 }
@@ -3,6 +3,7 @@
 use std::collections::HashMap;
 
 use crate::graph::property_value::PropertyValue;
+use crate::retry::RetryPolicy;
 use crate::schema::node::Node;
 use crate::schema::property::Property;
 use crate::value::Value;
@@ -16,6 +17,9 @@ pub struct PlacedNode {
     pub key: String,
     /// Assigned or default values for this node instance.
     pub values: HashMap<String, PropertyValue>,
+    /// Policy for retrying failed commands of this node instance, overriding any policy
+    /// the engine has for its schema.
+    pub retry: Option<RetryPolicy>,
 }
 
 impl PlacedNode {
@@ -38,6 +42,7 @@ impl PlacedNode {
                     )
                 })
                 .collect(),
+            retry: None,
         }
     }
 
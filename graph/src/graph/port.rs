@@ -0,0 +1,28 @@
+//! Ports expose data properties of nodes as parameters and results of a graph.
+
+use crate::value::DataType;
+
+/// Binds a graph's parameter or result to a node's data property.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Port {
+    /// Port's id.
+    pub id: String,
+    /// Port's data type.
+    pub data_type: DataType,
+    /// Key of a bound node.
+    pub key: String,
+    /// Id of a bound property.
+    pub property_id: String,
+}
+
+impl Port {
+    /// Constructs a `Port`.
+    pub fn new(id: &str, data_type: DataType, key: &str, property_id: &str) -> Self {
+        Port {
+            id: String::from(id),
+            data_type,
+            key: String::from(key),
+            property_id: String::from(property_id),
+        }
+    }
+}
@@ -1,5 +1,3 @@
-#![cfg_attr(feature = "clippy", feature(plugin))]
-#![cfg_attr(feature = "clippy", plugin(clippy))]
 #![warn(
     missing_docs,
     missing_copy_implementations,
@@ -16,5 +14,6 @@
 
 pub mod error;
 pub mod graph;
+pub mod retry;
 pub mod schema;
 pub mod value;
@@ -0,0 +1,120 @@
+//! Policies for retrying failed commands.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// Decides how long to wait before retrying a failed command.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backoff {
+    /// Waits the same time before every retry.
+    Fixed(Duration),
+    /// Doubles the wait after every retry, up to a maximum.
+    Exponential {
+        /// Wait before the first retry.
+        initial: Duration,
+        /// Longest wait.
+        max: Duration,
+    },
+}
+
+/// Decides whether and when a failed command is retried.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    backoff: Backoff,
+    jitter: f64,
+    retriable: Option<Vec<String>>,
+}
+
+impl RetryPolicy {
+    /// Constructs a `RetryPolicy` that runs a command at most `max_attempts` times with
+    /// no wait between attempts, retrying on any error.
+    pub fn new(max_attempts: u32) -> Self {
+        RetryPolicy {
+            max_attempts: max_attempts.max(1),
+            backoff: Backoff::Fixed(Duration::from_millis(0)),
+            jitter: 0.0,
+            retriable: None,
+        }
+    }
+
+    /// Waits the same time before every retry.
+    pub fn fixed(mut self, delay: Duration) -> Self {
+        self.backoff = Backoff::Fixed(delay);
+        self
+    }
+
+    /// Waits `initial` before the first retry and doubles the wait after every retry, up
+    /// to `max`.
+    pub fn exponential(mut self, initial: Duration, max: Duration) -> Self {
+        self.backoff = Backoff::Exponential { initial, max };
+        self
+    }
+
+    /// Randomly shortens every wait by up to a fraction of it, between 0 and 1.
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Only retries errors with a code. Can be called multiple times to retry several
+    /// kinds of errors. Without it, every error is retried.
+    pub fn retry_on(mut self, code: &str) -> Self {
+        self.retriable
+            .get_or_insert_with(Vec::new)
+            .push(String::from(code));
+        self
+    }
+
+    /// Returns the maximum number of attempts.
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Returns policy's backoff.
+    pub fn backoff(&self) -> Backoff {
+        self.backoff
+    }
+
+    /// Returns the fraction by which waits are randomly shortened.
+    pub fn jitter_fraction(&self) -> f64 {
+        self.jitter
+    }
+
+    /// Returns codes of retried errors, or `None` if every error is retried.
+    pub fn retried_codes(&self) -> Option<&[String]> {
+        self.retriable.as_deref()
+    }
+
+    /// Returns whether an error with a code can be retried.
+    pub fn is_retriable(&self, code: Option<&str>) -> bool {
+        match (&self.retriable, code) {
+            (None, _) => true,
+            (Some(codes), Some(code)) => codes.iter().any(|retried| retried == code),
+            (Some(_), None) => false,
+        }
+    }
+
+    /// Returns the wait before an attempt following a failed one, or `None` if the failed
+    /// attempt was the last one. Attempts are counted from 1.
+    pub fn delay(&self, failed_attempt: u32) -> Option<Duration> {
+        if failed_attempt >= self.max_attempts {
+            return None;
+        }
+        let delay = match self.backoff {
+            Backoff::Fixed(delay) => delay,
+            Backoff::Exponential { initial, max } => initial
+                .checked_mul(2u32.saturating_pow(failed_attempt - 1))
+                .map_or(max, |delay| delay.min(max)),
+        };
+        Some(delay.mul_f64(1.0 - self.jitter * random()))
+    }
+}
+
+/// Returns a pseudo-random number between 0 and 1.
+fn random() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u8(0);
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
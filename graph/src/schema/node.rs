@@ -3,10 +3,17 @@
 use std::collections::HashMap;
 
 use crate::schema::property::{
-    CommandProperty, EventProperty, InputProperty, OutputProperty, Property,
+    CommandProperty, EventProperty, Field, InputProperty, OutputProperty, Property,
 };
 use crate::value::DataType;
 
+/// Error message, carried by error events.
+pub const ERROR_FIELD_MESSAGE: &str = "message";
+/// Error code, or an empty string if error has none, carried by error events.
+pub const ERROR_FIELD_CODE: &str = "code";
+/// Id of the failed command, carried by error events.
+pub const ERROR_FIELD_COMMAND: &str = "command";
+
 /// Describes a node.
 #[derive(Debug, Clone)]
 pub struct Node {
@@ -14,9 +21,11 @@ pub struct Node {
     pub id: String,
     /// Node's properties by ids.
     pub properties: HashMap<String, Property>,
+    /// Event fired when a command of the node fails, if declared.
+    pub error_event: Option<String>,
 }
 
-impl<'a> Node {
+impl Node {
     /// Constructs a `NodeBuilder`.
     pub fn builder(id: &str) -> NodeBuilder {
         NodeBuilder::new(id)
@@ -34,6 +43,7 @@ impl<'a> NodeBuilder {
             node: Node {
                 id: String::from(id),
                 properties: Default::default(),
+                error_event: None,
             },
         }
     }
@@ -48,18 +58,50 @@ impl<'a> NodeBuilder {
 
     /// Declares a new command property.
     pub fn command(&'a mut self, id: &str) -> &'a mut Self {
+        self.command_with_parameters(id, &[])
+    }
+
+    /// Declares a new command property that expects parameters from triggering events.
+    pub fn command_with_parameters(&'a mut self, id: &str, parameters: &[Field]) -> &'a mut Self {
         self.property(Property::Command(CommandProperty {
             id: String::from(id),
+            parameters: parameters.to_vec(),
         }))
     }
 
     /// Declares a new event property.
     pub fn event(&'a mut self, id: &str) -> &'a mut Self {
+        self.event_with_payload(id, &[])
+    }
+
+    /// Declares a new event property that carries a payload.
+    pub fn event_with_payload(&'a mut self, id: &str, payload: &[Field]) -> &'a mut Self {
         self.property(Property::Event(EventProperty {
             id: String::from(id),
+            payload: payload.to_vec(),
         }))
     }
 
+    /// Declares an event fired when a command of the node fails. Its payload carries
+    /// `message`, `code` and `command` string fields.
+    /// # Panics
+    /// If node already has an error event.
+    pub fn error_event(&'a mut self, id: &str) -> &'a mut Self {
+        if let Some(existing) = &self.node.error_event {
+            panic!("duplicate error event '{}'", existing);
+        }
+        self.event_with_payload(
+            id,
+            &[
+                Field::new(ERROR_FIELD_MESSAGE, DataType::String),
+                Field::new(ERROR_FIELD_CODE, DataType::String),
+                Field::new(ERROR_FIELD_COMMAND, DataType::String),
+            ],
+        );
+        self.node.error_event = Some(String::from(id));
+        self
+    }
+
     /// Declares a new input property.
     pub fn input(&'a mut self, id: &str, data_type: DataType) -> &'a mut Self {
         self.property(Property::Input(InputProperty {
@@ -94,8 +136,27 @@ mod tests {
         assert_eq!(
             Property::Command(CommandProperty {
                 id: String::from("command"),
+                parameters: Vec::new(),
             }),
             *n1.properties.get(&String::from("command")).unwrap()
         )
     }
+
+    #[test]
+    fn error_event() {
+        let n1 = Node::builder("a")
+            .command("command")
+            .error_event("failed")
+            .build();
+
+        assert_eq!(n1.error_event, Some(String::from("failed")));
+        match n1.properties.get("failed").unwrap() {
+            Property::Event(event) => {
+                assert!(event.get_field(ERROR_FIELD_MESSAGE).is_some());
+                assert!(event.get_field(ERROR_FIELD_CODE).is_some());
+                assert!(event.get_field(ERROR_FIELD_COMMAND).is_some());
+            }
+            property => panic!("unexpected property {:?}", property),
+        }
+    }
 }
@@ -2,11 +2,22 @@
 
 use crate::value::DataType;
 
+/// Typed value carried by an event or expected by a command.
+#[derive(Debug, Clone, PartialOrd, PartialEq)]
+pub struct Field {
+    /// Field's id.
+    pub id: String,
+    /// Field's data type.
+    pub data_type: DataType,
+}
+
 /// Event can trigger a command.
 #[derive(Debug, Clone, PartialOrd, PartialEq)]
 pub struct EventProperty {
     /// Property's id.
     pub id: String,
+    /// Fields carried by the event when fired.
+    pub payload: Vec<Field>,
 }
 
 /// Command executes specific computation inside a node.
@@ -14,6 +25,8 @@ pub struct EventProperty {
 pub struct CommandProperty {
     /// Property's id.
     pub id: String,
+    /// Fields that triggering events must provide.
+    pub parameters: Vec<Field>,
 }
 
 /// Input data for a node.
@@ -47,6 +60,23 @@ pub enum Property {
     Output(OutputProperty),
 }
 
+impl Field {
+    /// Constructs a `Field`.
+    pub fn new(id: &str, data_type: DataType) -> Self {
+        Field {
+            id: String::from(id),
+            data_type,
+        }
+    }
+}
+
+impl EventProperty {
+    /// Returns a payload field by id.
+    pub fn get_field(&self, id: &str) -> Option<&Field> {
+        self.payload.iter().find(|field| field.id == id)
+    }
+}
+
 impl Property {
     /// Returns property's id.
     pub fn id(&self) -> &String {
@@ -70,47 +100,27 @@ impl Property {
 
     /// Returns whether property is `Property::Event`.
     pub fn is_event(&self) -> bool {
-        if let Property::Event(_) = self {
-            true
-        } else {
-            false
-        }
+        matches!(self, Property::Event(_))
     }
 
     /// Returns whether property is `Property::Command`.
     pub fn is_command(&self) -> bool {
-        if let Property::Command(_) = self {
-            true
-        } else {
-            false
-        }
+        matches!(self, Property::Command(_))
     }
 
     /// Returns whether property is `Property::Input`.
     pub fn is_input(&self) -> bool {
-        if let Property::Input(_) = self {
-            true
-        } else {
-            false
-        }
+        matches!(self, Property::Input(_))
     }
 
     /// Returns whether property is `Property::Output`.
     pub fn is_output(&self) -> bool {
-        if let Property::Output(_) = self {
-            true
-        } else {
-            false
-        }
+        matches!(self, Property::Output(_))
     }
 
     /// Returns whether property can be used as a source.
     pub fn is_source(&self) -> bool {
-        match self {
-            Property::Input(_) => true,
-            Property::Command(_) => true,
-            _ => false,
-        }
+        matches!(self, Property::Input(_) | Property::Command(_))
     }
 
     /// Returns whether property can be used as a target.
@@ -1,5 +1,7 @@
 //! Base constructs for working with values.
 
+use std::fmt::{Display, Error, Formatter};
+
 /// Value's data type.
 #[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
 pub enum DataType {
@@ -48,6 +50,17 @@ impl Value {
     }
 }
 
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            Value::Integer(value) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{}", value),
+            Value::Boolean(value) => write!(f, "{}", value),
+            Value::String(value) => write!(f, "{}", value),
+        }
+    }
+}
+
 impl From<i64> for Value {
     fn from(a: i64) -> Self {
         Value::Integer(a)
@@ -1,7 +1,13 @@
+#![allow(clippy::needless_borrow)]
+
+use std::collections::HashMap;
 use std::panic::catch_unwind;
 
+use graph::graph::edge::Hook;
 use graph::graph::Graph;
+use graph::retry::RetryPolicy;
 use graph::schema::node::Node;
+use graph::schema::property::Field;
 use graph::schema::Schema;
 use graph::value::{DataType, Value};
 
@@ -98,6 +104,188 @@ fn errors() {
     .is_err());
 }
 
+#[test]
+fn remove_node() {
+    let schema = build_schema();
+    let mut graph = {
+        let mut graph_builder = Graph::builder(&schema);
+        let a1 = graph_builder.node(NODE_A, "a1").unwrap();
+        let b1 = graph_builder.node(NODE_B, "b1").unwrap();
+        let c1 = graph_builder.node(NODE_C, "c1").unwrap();
+        graph_builder.connect(&a1, EVENT, &b1, COMMAND).unwrap();
+        graph_builder
+            .connect(&b1, OUTPUT_INTEGER, &c1, INPUT_INTEGER)
+            .unwrap();
+        assert!(graph_builder
+            .connect(&b1, OUTPUT_INTEGER, &c1, INPUT_INTEGER)
+            .is_err());
+        graph_builder.build().unwrap()
+    };
+
+    let b1 = graph.get_node("b1").clone();
+    let c1 = graph.get_node("c1").clone();
+    let output = Hook::new(b1.clone(), b1.get_property(OUTPUT_INTEGER).clone());
+    let input = Hook::new(c1.clone(), c1.get_property(INPUT_INTEGER).clone());
+    assert_eq!(graph.edge_map.get_outputs(&output).len(), 1);
+    assert!(graph.edge_map.get_input(&input).is_some());
+
+    graph.remove_node("b1");
+    assert_eq!(graph.nodes.len(), 2);
+    assert!(graph.edge_map.edges.is_empty());
+    assert!(graph.edge_map.get_outputs(&output).is_empty());
+    assert!(graph.edge_map.get_input(&input).is_none());
+}
+
+#[test]
+fn remove_fan_in_edge() {
+    let schema = build_schema();
+    let mut graph = {
+        let mut graph_builder = Graph::builder(&schema);
+        let b1 = graph_builder.node(NODE_B, "b1").unwrap();
+        let b2 = graph_builder.node(NODE_B, "b2").unwrap();
+        let c1 = graph_builder.node(NODE_C, "c1").unwrap();
+        graph_builder
+            .connect(&b1, OUTPUT_INTEGER, &c1, INPUT_INTEGER)
+            .unwrap();
+        graph_builder
+            .connect(&b2, OUTPUT_INTEGER, &c1, INPUT_INTEGER)
+            .unwrap();
+        graph_builder.build().unwrap()
+    };
+
+    let c1 = graph.get_node("c1").clone();
+    let input = Hook::new(c1.clone(), c1.get_property(INPUT_INTEGER).clone());
+    assert_eq!(graph.edge_map.get_input(&input).unwrap().node.key, "b2");
+    graph.remove_node("b2");
+    assert_eq!(graph.edge_map.get_input(&input).unwrap().node.key, "b1");
+    graph.remove_node("b1");
+    assert!(graph.edge_map.get_input(&input).is_none());
+}
+
+#[test]
+fn retry() {
+    let schema = build_schema();
+    let mut graph_builder = Graph::builder(&schema);
+    let a1 = graph_builder.node(NODE_A, "a1").unwrap();
+    let b1 = graph_builder.node(NODE_B, "b1").unwrap();
+    graph_builder.retry(&a1, RetryPolicy::new(3)).unwrap();
+    let mut graph = graph_builder.build().unwrap();
+
+    assert_eq!(graph.get_node("a1").retry, Some(RetryPolicy::new(3)));
+    assert_eq!(graph.get_node("b1").retry, None);
+    assert!(graph.set_retry_policy("x1", RetryPolicy::new(2)).is_err());
+    graph.remove_node(&b1.key);
+    assert!(graph
+        .set_retry_policy(&b1.key, RetryPolicy::new(2))
+        .is_err());
+}
+
+#[test]
+fn payload() {
+    let schema = Schema::builder()
+        .node(
+            Node::builder(NODE_A)
+                .event_with_payload(EVENT, &[Field::new("count", DataType::Integer)])
+                .build(),
+        )
+        .node(
+            Node::builder(NODE_B)
+                .command_with_parameters(COMMAND, &[Field::new("count", DataType::Integer)])
+                .build(),
+        )
+        .node(
+            Node::builder(NODE_C)
+                .command_with_parameters(COMMAND, &[Field::new("count", DataType::String)])
+                .event(EVENT)
+                .build(),
+        )
+        .build();
+    let mut graph_builder = Graph::builder(&schema);
+    let a1 = graph_builder.node(NODE_A, "a1").unwrap();
+    let b1 = graph_builder.node(NODE_B, "b1").unwrap();
+    let c1 = graph_builder.node(NODE_C, "c1").unwrap();
+    let c2 = graph_builder.node(NODE_C, "c2").unwrap();
+
+    assert!(graph_builder.connect(&a1, EVENT, &b1, COMMAND).is_ok());
+    assert!(graph_builder.connect(&a1, EVENT, &c1, COMMAND).is_err());
+    assert!(graph_builder.connect(&c2, EVENT, &c1, COMMAND).is_err());
+}
+
+#[test]
+fn guard() {
+    let schema = Schema::builder()
+        .node(
+            Node::builder(NODE_A)
+                .event_with_payload(EVENT, &[Field::new("count", DataType::Integer)])
+                .output(OUTPUT_STRING, DataType::String)
+                .build(),
+        )
+        .node(Node::builder(NODE_B).command(COMMAND).build())
+        .build();
+    let mut graph_builder = Graph::builder(&schema);
+    let a1 = graph_builder.node(NODE_A, "a1").unwrap();
+    let b1 = graph_builder.node(NODE_B, "b1").unwrap();
+    let b2 = graph_builder.node(NODE_B, "b2").unwrap();
+
+    assert!(graph_builder
+        .connect_with_guard(&a1, EVENT, &b1, COMMAND, "count > 2")
+        .is_ok());
+    assert!(graph_builder
+        .connect_with_guard(&a1, EVENT, &b2, COMMAND, "count > \"2\"")
+        .is_err());
+    assert!(graph_builder
+        .connect_with_guard(&a1, EVENT, &b2, COMMAND, "missing > 2")
+        .is_err());
+    assert!(graph_builder
+        .connect_with_guard(&a1, EVENT, &b2, COMMAND, "output-string == \"x\"")
+        .is_ok());
+
+    let graph = graph_builder.build().unwrap();
+    let edge = graph.edge_map.edges.get("a1#event>b1#command").unwrap();
+    assert_eq!(edge.guard.as_ref().unwrap().to_string(), "count > 2");
+}
+
+#[test]
+fn ports() {
+    let schema = build_schema();
+    let mut graph = {
+        let mut graph_builder = Graph::builder(&schema);
+        let b1 = graph_builder.node(NODE_B, "b1").unwrap();
+        let c1 = graph_builder.node(NODE_C, "c1").unwrap();
+        graph_builder.parameter("x", &b1, INPUT_INTEGER).unwrap();
+        graph_builder.result("y", &c1, OUTPUT_INTEGER).unwrap();
+        assert!(graph_builder.parameter("x", &c1, INPUT_INTEGER).is_err());
+        assert!(graph_builder.parameter("z", &c1, OUTPUT_INTEGER).is_err());
+        assert!(graph_builder.result("z", &c1, COMMAND).is_err());
+        graph_builder.build().unwrap()
+    };
+
+    let mut arguments = HashMap::new();
+    assert!(graph.bind(&arguments).is_err());
+    arguments.insert(String::from("x"), Value::from(42));
+    graph.bind(&arguments).unwrap();
+    assert_eq!(
+        graph
+            .get_node("b1")
+            .values
+            .get(INPUT_INTEGER)
+            .unwrap()
+            .value,
+        Value::from(42)
+    );
+    arguments.insert(String::from("w"), Value::from(1));
+    assert!(graph.bind(&arguments).is_err());
+
+    let mut graph_builder = Graph::builder(&schema);
+    let b1 = graph_builder.node(NODE_B, "b1").unwrap();
+    let c1 = graph_builder.node(NODE_C, "c1").unwrap();
+    graph_builder.parameter("x", &c1, INPUT_INTEGER).unwrap();
+    graph_builder
+        .connect(&b1, OUTPUT_INTEGER, &c1, INPUT_INTEGER)
+        .unwrap();
+    assert!(graph_builder.build().is_err());
+}
+
 fn build_schema() -> Schema {
     Schema::builder()
         .node(